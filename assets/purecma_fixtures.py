@@ -0,0 +1,108 @@
+"""Records purecma trajectories as JSON fixtures for `tests/purecma.rs`.
+
+Each generation stores the state before `tell` (mean, sigma, C, ps, pc and
+the eigensystem used by `ask`), the standard normal samples z, candidates
+and f-values, and the state after `tell`.
+
+Usage:
+    pip install cma
+    python assets/purecma_fixtures.py
+
+Trajectories come from `cma.purecma.CMAES`, the reference implementation the
+tests compare against; the script refuses to run without the `cma` package.
+Each fixture records the `cma` version that produced it.
+"""
+
+import json
+import os
+import random
+import sys
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+OUT = os.path.join(HERE, "..", "tests", "fixtures")
+
+
+def sphere(x):
+    return sum(xi ** 2 for xi in x)
+
+
+def ellipsoid(x):
+    n = len(x)
+    return sum(1e3 ** (i / (n - 1)) * xi ** 2 for i, xi in enumerate(x))
+
+
+def rosenbrock(x):
+    return sum(100 * (x[i] ** 2 - x[i + 1]) ** 2 + (x[i] - 1) ** 2 for i in range(len(x) - 1))
+
+
+def record(make, version, name, fun, dim, popsize, sigma, generations, seed):
+    random.seed(seed)
+    xstart = [random.uniform(-2, 2) for _ in range(dim)]
+    es = make(xstart, sigma, popsize)
+    gens = []
+    for _ in range(generations):
+        X = es.ask()
+        C = es.C
+        B, d = C.eigenbasis, C.eigenvalues
+        before = {
+            "counteval": es.counteval,
+            "mean": list(es.xmean),
+            "sigma": es.sigma,
+            "cov": [list(row) for row in C],
+            "ps": list(es.ps),
+            "pc": list(es.pc),
+            "inv_sqrt": [list(row) for row in C.invsqrt],
+            "eig_vecs": [list(row) for row in B],
+            "eig_vals": list(d),
+        }
+        # Standard normal samples behind each candidate
+        z = [[sum(B[j][i] * (x[j] - es.xmean[j]) for j in range(dim)) / (es.sigma * d[i] ** 0.5)
+              for i in range(dim)] for x in X]
+        f = [fun(x) for x in X]
+        es.tell(X, f)
+        gens.append({
+            "before": before,
+            "z": z,
+            "x": [list(x) for x in X],
+            "f": f,
+            "after": {
+                "mean": list(es.xmean),
+                "sigma": es.sigma,
+                "cov": [list(row) for row in es.C],
+                "ps": list(es.ps),
+                "pc": list(es.pc),
+            },
+        })
+    fixture = {
+        "generator": "purecma",
+        "cma_version": version,
+        "function": name,
+        "dim": dim,
+        "popsize": popsize,
+        "seed": seed,
+        "generations": gens,
+    }
+    path = os.path.join(OUT, "purecma_%s_%dd.json" % (name, dim))
+    with open(path, "w") as fh:
+        json.dump(fixture, fh)
+    print("Wrote %s (%d generations, cma %s)" % (path, generations, version))
+
+
+def main():
+    try:
+        import cma
+        from cma import purecma
+    except ImportError:
+        sys.exit("cma is required to record the fixtures: pip install cma")
+
+    def make(xstart, sigma, popsize):
+        return purecma.CMAES(xstart, sigma, popsize=popsize)
+
+    os.makedirs(OUT, exist_ok=True)
+    record(make, cma.__version__, "sphere", sphere, 4, 8, 0.5, 40, 1)
+    record(make, cma.__version__, "ellipsoid", ellipsoid, 4, 8, 0.5, 60, 2)
+    record(make, cma.__version__, "rosenbrock", rosenbrock, 3, 7, 0.3, 60, 3)
+
+
+if __name__ == "__main__":
+    main()
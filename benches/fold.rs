@@ -70,7 +70,7 @@ fn fold() {
 }
 
 fn cmaes_benchmark(c: &mut Criterion) {
-    c.bench_function("CMA-ES FOLD", |b| b.iter(|| fold()));
+    c.bench_function("CMA-ES FOLD", |b| b.iter(fold));
 }
 
 criterion_group!(benches, cmaes_benchmark);
@@ -90,7 +90,7 @@ fn ask_tell() {
 }
 
 fn cmaes_benchmark(c: &mut Criterion) {
-    c.bench_function("CMA-ES Mine", |b| b.iter(|| ask_tell()));
+    c.bench_function("CMA-ES Mine", |b| b.iter(ask_tell));
 }
 
 criterion_group!(benches, cmaes_benchmark);
@@ -9,7 +9,7 @@ include!("../assets/pengowen.rs");
 ///
 /// * `c` - A mutable reference to a `Criterion` object for benchmarking.
 fn cmaes_benchmark(c: &mut Criterion) {
-    c.bench_function("CMA-ES Pengowen", |b| b.iter(|| example()));
+    c.bench_function("CMA-ES Pengowen", |b| b.iter(example));
 }
 
 criterion_group!(benches, cmaes_benchmark);
@@ -0,0 +1,80 @@
+use haru_cmaes::fitness::{FitnessFunction, MinOrMax, PopulationY};
+use haru_cmaes::objectives::Rosenbrock;
+use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+use haru_cmaes::strategy::CmaesAlgo;
+use haru_cmaes::tickets::{Stragglers, Submission, TicketDriver};
+use nalgebra::DMatrix;
+use std::env::var;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+fn main() {
+    // Define verbose or not
+    let verbose = var("VERBOSE").unwrap_or("No".to_string());
+
+    // Objective evaluated by workers, with variable latency
+    let obj_func = Arc::new(Rosenbrock {
+        obj_dim: 4,
+        dir: MinOrMax::Min,
+    });
+
+    let params = CmaesParams::new()
+        .and_then(|p| p.set_popsize(12))
+        .and_then(|p| p.set_xstart(obj_func.cost_dim(), 0.0))
+        .and_then(|p| p.set_sigma(0.5))
+        .unwrap();
+    let cmaes = CmaesAlgo::new(params).unwrap();
+    let state = CmaesState::init_state(&cmaes.params).unwrap();
+
+    // Missing results are handed out again after 20 ms
+    let mut driver =
+        TicketDriver::new(cmaes, state, Stragglers::Reissue(Duration::from_millis(20))).unwrap();
+
+    // Workers take tickets from a shared queue and send back (id, value)
+    let (job_tx, job_rx) = mpsc::channel::<(u64, Vec<f32>)>();
+    let (result_tx, result_rx) = mpsc::channel::<(u64, f32)>();
+    let job_rx = Arc::new(Mutex::new(job_rx));
+    for _ in 0..8 {
+        let (job_rx, result_tx, obj_func) = (job_rx.clone(), result_tx.clone(), obj_func.clone());
+        thread::spawn(move || loop {
+            let Ok((id, x)) = job_rx.lock().unwrap().recv() else {
+                return;
+            };
+            thread::sleep(Duration::from_millis(fastrand::u64(0..10)));
+            let pop = PopulationY {
+                y: DMatrix::from_row_slice(1, x.len(), &x),
+            };
+            let _ = result_tx.send((id, obj_func.cost(&pop)[0]));
+        });
+    }
+
+    let (mut told, mut stale) = (0, 0);
+    while driver.state().g < 300 && driver.state().best_y_fit[0] > 1e-8 {
+        // Hand out everything available, then wait for any result
+        while let Some(ticket) = driver.ask_one().unwrap() {
+            job_tx
+                .send((ticket.id, ticket.x.iter().copied().collect()))
+                .unwrap();
+        }
+        if let Ok((id, value)) = result_rx.recv_timeout(Duration::from_millis(5)) {
+            match driver.submit(id, value).unwrap() {
+                Submission::Told => told += 1,
+                Submission::Stale => stale += 1,
+                Submission::Pending => {}
+            }
+        }
+    }
+
+    if verbose != "No" {
+        println!(
+            "Generations told: {} | Stale results: {} | Fitness: {:+.5e}",
+            told,
+            stale,
+            driver.state().best_y_fit[0]
+        );
+        println!("{:+.5?}", driver.state().best_y);
+    }
+}
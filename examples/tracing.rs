@@ -0,0 +1,33 @@
+use haru_cmaes::fitness::MinOrMax;
+use haru_cmaes::objectives::Rosenbrock;
+use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+use haru_cmaes::strategy::{CmaesAlgo, CmaesAlgoOptimizer};
+use tracing_subscriber::fmt::format::FmtSpan;
+
+// Run with: cargo run --example tracing --features tracing
+fn main() {
+    // Print events of debug level and up, and when spans close
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_span_events(FmtSpan::CLOSE)
+        .init();
+
+    // Create cost function
+    let obj_func = Rosenbrock {
+        obj_dim: 5,
+        dir: MinOrMax::Min,
+    };
+
+    // Initialize CMA-ES parameters
+    let params = CmaesParams::new()
+        .and_then(|p| p.set_xstart(5, 0.0))
+        .and_then(|p| p.set_num_gens(50))
+        .unwrap();
+    let cmaes = CmaesAlgo::new(params).unwrap();
+    let state = CmaesState::init_state(&cmaes.params).unwrap();
+
+    // Every ask, evaluation and tell is traced
+    let state = cmaes.rollout_fold(state, obj_func).unwrap();
+    println!("Best fitness {:+.5?}", state.best_y_fit[0]);
+}
@@ -0,0 +1,583 @@
+use crate::fitness::{FitnessFunction, MinOrMax, PopulationY};
+use crate::objectives::KnownOptimum;
+use anyhow::{ensure, Result};
+use nalgebra::{DMatrix, DVector};
+use std::f64::consts::PI;
+
+/// Uniform numbers from the legacy BBOB 2009 generator.
+pub fn bbob_unif(n: usize, seed: i64) -> Vec<f64> {
+    let mut aktseed = seed.abs().max(1);
+    let mut rgrand = [0i64; 32];
+    let next = |aktseed: i64| {
+        let tmp = aktseed / 127773;
+        let next = 16807 * (aktseed - tmp * 127773) - 2836 * tmp;
+        if next < 0 {
+            next + 2147483647
+        } else {
+            next
+        }
+    };
+    for i in (0..40).rev() {
+        aktseed = next(aktseed);
+        if i < 32 {
+            rgrand[i] = aktseed;
+        }
+    }
+    let mut aktrand = rgrand[0];
+    (0..n)
+        .map(|_| {
+            aktseed = next(aktseed);
+            let tmp = (aktrand / 67108865) as usize;
+            aktrand = rgrand[tmp];
+            rgrand[tmp] = aktseed;
+            let r = aktrand as f64 / 2.147483647e9;
+            if r == 0. {
+                1e-99
+            } else {
+                r
+            }
+        })
+        .collect()
+}
+
+/// Gaussian numbers from the legacy BBOB 2009 generator.
+pub fn bbob_gauss(n: usize, seed: i64) -> Vec<f64> {
+    let unif = bbob_unif(2 * n, seed);
+    (0..n)
+        .map(|i| {
+            let g = (-2. * unif[i].ln()).sqrt() * (2. * PI * unif[n + i]).cos();
+            if g == 0. {
+                1e-99
+            } else {
+                g
+            }
+        })
+        .collect()
+}
+
+/// Random orthogonal matrix, Gram-Schmidt on the columns of a Gaussian matrix.
+pub fn bbob_rotation(seed: i64, dim: usize) -> DMatrix<f64> {
+    let gauss = bbob_gauss(dim * dim, seed);
+    let mut b = DMatrix::from_fn(dim, dim, |i, j| gauss[j * dim + i]);
+    for i in 0..dim {
+        for j in 0..i {
+            let prod: f64 = (0..dim).map(|k| b[(k, i)] * b[(k, j)]).sum();
+            for k in 0..dim {
+                b[(k, i)] -= prod * b[(k, j)];
+            }
+        }
+        let norm: f64 = (0..dim).map(|k| b[(k, i)] * b[(k, i)]).sum::<f64>().sqrt();
+        for k in 0..dim {
+            b[(k, i)] /= norm;
+        }
+    }
+    b
+}
+
+/// Optimum location, uniform in [-4, 4] on a 1e-4 grid.
+pub fn bbob_xopt(seed: i64, dim: usize) -> DVector<f64> {
+    DVector::from_iterator(
+        dim,
+        bbob_unif(dim, seed).into_iter().map(|u| {
+            let x = 8. * (1e4 * u).floor() / 1e4 - 4.;
+            if x == 0. {
+                -1e-5
+            } else {
+                x
+            }
+        }),
+    )
+}
+
+/// Optimal value, a Cauchy-like draw clipped to [-1000, 1000].
+pub fn bbob_fopt(function: usize, instance: usize) -> f64 {
+    let rseed = match function {
+        4 => 3,
+        18 => 17,
+        f => f as i64,
+    } + 10000 * instance as i64;
+    let gval = bbob_gauss(1, rseed)[0];
+    let gval2 = bbob_gauss(1, rseed + 1)[0];
+    ((100. * 100. * gval / gval2 + 0.5).floor() / 100.).clamp(-1000., 1000.)
+}
+
+fn t_osz_scalar(x: f64) -> f64 {
+    if x == 0. {
+        return 0.;
+    }
+    let xhat = x.abs().ln();
+    let (c1, c2) = if x > 0. { (10., 7.9) } else { (5.5, 3.1) };
+    x.signum() * (xhat + 0.049 * ((c1 * xhat).sin() + (c2 * xhat).sin())).exp()
+}
+
+fn t_osz(x: &DVector<f64>) -> DVector<f64> {
+    x.map(t_osz_scalar)
+}
+
+fn t_asy(x: &DVector<f64>, beta: f64) -> DVector<f64> {
+    let d = x.len();
+    DVector::from_fn(d, |i, _| {
+        if x[i] > 0. {
+            x[i].powf(1. + beta * ratio(i, d) * x[i].sqrt())
+        } else {
+            x[i]
+        }
+    })
+}
+
+/// Position `i / (d - 1)` of coordinate `i` in `[0, 1]`.
+fn ratio(i: usize, d: usize) -> f64 {
+    i as f64 / (d as f64 - 1.)
+}
+
+/// Diagonal of the conditioning matrix with condition `alpha`.
+fn lambda(alpha: f64, d: usize) -> DVector<f64> {
+    DVector::from_fn(d, |i, _| alpha.sqrt().powf(ratio(i, d)))
+}
+
+fn f_pen(x: &DVector<f64>) -> f64 {
+    x.iter().map(|xi| (xi.abs() - 5.).max(0.).powi(2)).sum()
+}
+
+fn rastrigin(z: &DVector<f64>) -> f64 {
+    10. * (z.len() as f64 - z.iter().map(|zi| (2. * PI * zi).cos()).sum::<f64>()) + z.norm_squared()
+}
+
+fn ellipsoid(z: &DVector<f64>) -> f64 {
+    let d = z.len();
+    (0..d).map(|i| 1e6f64.powf(ratio(i, d)) * z[i] * z[i]).sum()
+}
+
+fn rosenbrock(z: &DVector<f64>) -> f64 {
+    (0..z.len() - 1)
+        .map(|i| 100. * (z[i] * z[i] - z[i + 1]).powi(2) + (z[i] - 1.).powi(2))
+        .sum()
+}
+
+/// Peaks of the Gallagher functions.
+#[derive(Debug, Clone)]
+struct Peaks {
+    x_local: DMatrix<f64>, // Peak locations, one column per peak
+    scales: DMatrix<f64>,  // Peak conditioning, one row per peak
+    values: DVector<f64>,  // Peak heights
+}
+
+impl Peaks {
+    fn new(rseed: i64, dim: usize, num_peaks: usize, rot: &DMatrix<f64>) -> Self {
+        let (b, c, max_cond1) = if num_peaks == 101 {
+            (10., 5., 1000f64.sqrt())
+        } else {
+            (9.8, 4.9, 1000.)
+        };
+        let argsort = |r: &[f64]| {
+            let mut idx: Vec<usize> = (0..r.len()).collect();
+            idx.sort_by(|&i, &j| r[i].partial_cmp(&r[j]).unwrap());
+            idx
+        };
+
+        // Random permutation of the conditions and linear peak heights
+        let perm = argsort(&bbob_unif(num_peaks - 1, rseed));
+        let cond = DVector::from_fn(num_peaks, |i, _| {
+            if i == 0 {
+                max_cond1
+            } else {
+                1000f64.powf(perm[i - 1] as f64 / (num_peaks as f64 - 2.))
+            }
+        });
+        let values = DVector::from_fn(num_peaks, |i, _| {
+            if i == 0 {
+                10.
+            } else {
+                (i as f64 - 1.) / (num_peaks as f64 - 2.) * 8. + 1.1
+            }
+        });
+
+        // Random permutation of the axis scales per peak
+        let mut scales = DMatrix::zeros(num_peaks, dim);
+        for i in 0..num_peaks {
+            let perm = argsort(&bbob_unif(dim, rseed + 1000 * i as i64));
+            for j in 0..dim {
+                scales[(i, j)] = cond[i].powf(perm[j] as f64 / (dim as f64 - 1.) - 0.5);
+            }
+        }
+
+        // Rotated peak locations, the first (global) one closer to the origin
+        let r = bbob_unif(dim * num_peaks, rseed);
+        let locs = DMatrix::from_fn(dim, num_peaks, |k, j| b * r[j * dim + k] - c);
+        let mut x_local = rot * locs;
+        x_local.column_mut(0).scale_mut(0.8);
+
+        Peaks {
+            x_local,
+            scales,
+            values,
+        }
+    }
+}
+
+/// Noiseless BBOB benchmark functions f1 to f24.
+///
+/// Instances reproduce the random shifts, rotations and optimal values of
+/// the legacy BBOB 2009 code used by COCO, so results can be compared with
+/// published data. Values are computed in `f64` and include `fopt`; since
+/// fitness is `f32`, set `fopt` to zero to optimize `f - fopt` at full
+/// resolution.
+///
+/// ```rust
+/// use haru_cmaes::bbob::Bbob;
+/// use haru_cmaes::fitness::{FitnessEvaluator, PopulationY};
+/// use nalgebra::DMatrix;
+///
+/// for function in 1..=24 {
+///     let f = Bbob::new(function, 5, 1).unwrap();
+///
+///     // The optimum is where it is said to be
+///     assert!((f.value(f.xopt.as_slice()) - f.fopt).abs() < 1e-6);
+///
+///     // And nothing random does better
+///     let y = DMatrix::from_fn(20, 5, |_, _| 10. * fastrand::f32() - 5.);
+///     let fitness = f.evaluate(&PopulationY { y }).unwrap();
+///     assert!(fitness.values.min() as f64 >= f.fopt - 1e-3);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Bbob {
+    pub function: usize,
+    pub instance: usize,
+    pub obj_dim: usize,
+    pub dir: MinOrMax,
+    pub xopt: DVector<f64>, // Optimum location
+    pub fopt: f64,          // Optimal value
+    rot1: DMatrix<f64>,     // Rotation drawn with seed + 1e6
+    rot2: DMatrix<f64>,     // Rotation drawn with seed
+    peaks: Option<Peaks>,   // Gallagher peaks
+}
+
+impl Bbob {
+    /// Creates `function` (1 to 24) in dimension `obj_dim` for a given instance.
+    ///
+    /// ```rust
+    /// use haru_cmaes::bbob::Bbob;
+    ///
+    /// let f = Bbob::new(1, 2, 1).unwrap();
+    ///
+    /// assert_eq!(f.fopt, 79.48);
+    /// assert!(Bbob::new(25, 2, 1).is_err());
+    /// ```
+    pub fn new(function: usize, obj_dim: usize, instance: usize) -> Result<Self> {
+        ensure!(
+            (1..=24).contains(&function),
+            "BBOB functions are numbered 1 to 24"
+        );
+        ensure!(obj_dim >= 2, "BBOB functions require at least 2 dimensions");
+        let d = obj_dim;
+        let rseed = match function {
+            4 => 3,
+            18 => 17,
+            f => f as i64,
+        } + 10000 * instance as i64;
+        let rot1 = bbob_rotation(rseed + 1_000_000, d);
+        let rot2 = bbob_rotation(rseed, d);
+        let factor = (d as f64).sqrt().max(8.) / 8.;
+        let signs = |r: Vec<f64>, threshold: f64, magnitude: f64| {
+            DVector::from_iterator(
+                d,
+                r.into_iter()
+                    .map(|u| if u < threshold { -magnitude } else { magnitude }),
+            )
+        };
+        let mut peaks = None;
+
+        let xopt = match function {
+            4 => bbob_xopt(rseed, d)
+                .map_with_location(|i, _, x| if i % 2 == 0 { x.abs() } else { x }),
+            5 => bbob_xopt(rseed, d).map(|x| if x < 0. { -5. } else { 5. }),
+            8 => bbob_xopt(rseed, d) * 0.75,
+            9 | 19 => rot2.transpose() * DVector::from_element(d, 0.5 / factor),
+            12 => bbob_xopt(rseed + 1_000_000, d),
+            20 => signs(bbob_unif(d, rseed), 0.5, 0.5 * 4.2096874637),
+            21 | 22 => {
+                let num_peaks = if function == 21 { 101 } else { 21 };
+                let (b, c) = if function == 21 {
+                    (10., 5.)
+                } else {
+                    (9.8, 4.9)
+                };
+                peaks = Some(Peaks::new(rseed, d, num_peaks, &rot2));
+                DVector::from_iterator(
+                    d,
+                    bbob_unif(d, rseed).into_iter().map(|r| 0.8 * (b * r - c)),
+                )
+            }
+            24 => signs(bbob_gauss(d, rseed), 0.0, 0.5 * 2.5),
+            _ => bbob_xopt(rseed, d),
+        };
+
+        Ok(Self {
+            function,
+            instance,
+            obj_dim,
+            dir: MinOrMax::Min,
+            xopt,
+            fopt: bbob_fopt(function, instance),
+            rot1,
+            rot2,
+            peaks,
+        })
+    }
+
+    /// `rot1 * diag(lambda) * rot2`
+    fn conditioned(&self, alpha: f64) -> DMatrix<f64> {
+        &self.rot1 * DMatrix::from_diagonal(&lambda(alpha, self.obj_dim)) * &self.rot2
+    }
+
+    /// Function value at `x`, including `fopt`.
+    pub fn value(&self, x: &[f64]) -> f64 {
+        let d = self.obj_dim;
+        let x = DVector::from_column_slice(x);
+        let shifted: DVector<f64> = &x - &self.xopt;
+        let factor = (d as f64).sqrt().max(8.) / 8.;
+        let raw = match self.function {
+            // Sphere
+            1 => shifted.norm_squared(),
+            // Separable ellipsoid
+            2 => ellipsoid(&t_osz(&shifted)),
+            // Separable Rastrigin
+            3 => rastrigin(&t_asy(&t_osz(&shifted), 0.2).component_mul(&lambda(10., d))),
+            // Bueche-Rastrigin
+            4 => {
+                let y = t_osz(&shifted);
+                let z = DVector::from_fn(d, |i, _| {
+                    let s = 10f64.sqrt().powf(ratio(i, d));
+                    if i % 2 == 0 && y[i] > 0. {
+                        10. * s * y[i]
+                    } else {
+                        s * y[i]
+                    }
+                });
+                rastrigin(&z) + 100. * f_pen(&x)
+            }
+            // Linear slope
+            5 => (0..d)
+                .map(|i| {
+                    let s = self.xopt[i].signum() * 10f64.powf(ratio(i, d));
+                    let zi = if x[i] * self.xopt[i] < 25. {
+                        x[i]
+                    } else {
+                        self.xopt[i]
+                    };
+                    5. * s.abs() - s * zi
+                })
+                .sum(),
+            // Attractive sector
+            6 => {
+                let z = self.conditioned(10.) * &shifted;
+                let s: f64 = (0..d)
+                    .map(|i| {
+                        let w = if self.xopt[i] * z[i] > 0. { 1e4 } else { 1. };
+                        w * z[i] * z[i]
+                    })
+                    .sum();
+                t_osz_scalar(s).powf(0.9)
+            }
+            // Step ellipsoid
+            7 => {
+                let zhat: DVector<f64> = (&self.rot2 * &shifted).component_mul(&lambda(10., d));
+                let ztilde = zhat.map(|z| {
+                    if z.abs() > 0.5 {
+                        (z + 0.5).floor()
+                    } else {
+                        (10. * z + 0.5).floor() / 10.
+                    }
+                });
+                let z = &self.rot1 * ztilde;
+                let sum: f64 = (0..d).map(|i| 100f64.powf(ratio(i, d)) * z[i] * z[i]).sum();
+                0.1 * (zhat[0].abs() * 1e-4).max(sum) + f_pen(&x)
+            }
+            // Rosenbrock
+            8 => rosenbrock(&shifted.map(|s| factor * s + 1.)),
+            // Rotated Rosenbrock
+            9 => rosenbrock(&(&self.rot2 * &x).map(|s| factor * s + 0.5)),
+            // Ellipsoid
+            10 => ellipsoid(&t_osz(&(&self.rot1 * &shifted))),
+            // Discus
+            11 => {
+                let z = t_osz(&(&self.rot1 * &shifted));
+                1e6 * z[0] * z[0] + z.rows(1, d - 1).norm_squared()
+            }
+            // Bent cigar
+            12 => {
+                let z = &self.rot1 * t_asy(&(&self.rot1 * &shifted), 0.5);
+                z[0] * z[0] + 1e6 * z.rows(1, d - 1).norm_squared()
+            }
+            // Sharp ridge
+            13 => {
+                let z = self.conditioned(10.) * &shifted;
+                z[0] * z[0] + 100. * z.rows(1, d - 1).norm()
+            }
+            // Different powers
+            14 => {
+                let z = &self.rot1 * &shifted;
+                (0..d)
+                    .map(|i| z[i].abs().powf(2. + 4. * ratio(i, d)))
+                    .sum::<f64>()
+                    .sqrt()
+            }
+            // Rastrigin
+            15 => {
+                let y = t_asy(&t_osz(&(&self.rot1 * &shifted)), 0.2);
+                rastrigin(&(self.conditioned(10.) * y))
+            }
+            // Weierstrass
+            16 => {
+                let z = self.conditioned(0.01) * t_osz(&(&self.rot1 * &shifted));
+                let f0: f64 = (0..12)
+                    .map(|k| 0.5f64.powi(k) * (PI * 3f64.powi(k)).cos())
+                    .sum();
+                let sum: f64 = z
+                    .iter()
+                    .map(|zi| {
+                        (0..12)
+                            .map(|k| 0.5f64.powi(k) * (2. * PI * 3f64.powi(k) * (zi + 0.5)).cos())
+                            .sum::<f64>()
+                    })
+                    .sum();
+                10. * (sum / d as f64 - f0).powi(3) + 10. / d as f64 * f_pen(&x)
+            }
+            // Schaffers F7, condition 10 and 1000
+            17 | 18 => {
+                let cond = if self.function == 17 { 10. } else { 1000. };
+                let z = (&self.rot2 * t_asy(&(&self.rot1 * &shifted), 0.5))
+                    .component_mul(&lambda(cond, d));
+                let sum: f64 = (0..d - 1)
+                    .map(|i| {
+                        let s2 = z[i] * z[i] + z[i + 1] * z[i + 1];
+                        s2.powf(0.25) * (1. + (50. * s2.powf(0.1)).sin().powi(2))
+                    })
+                    .sum();
+                (sum / (d as f64 - 1.)).powi(2) + 10. * f_pen(&x)
+            }
+            // Composite Griewank-Rosenbrock F8F2
+            19 => {
+                let z = (&self.rot2 * &x).map(|s| factor * s + 0.5);
+                let sum: f64 = (0..d - 1)
+                    .map(|i| {
+                        let s = 100. * (z[i] * z[i] - z[i + 1]).powi(2) + (z[i] - 1.).powi(2);
+                        s / 4000. - s.cos()
+                    })
+                    .sum();
+                10. + 10. * sum / (d as f64 - 1.)
+            }
+            // Schwefel x sin(x)
+            20 => {
+                let abs2 = self.xopt.map(|xo| 2. * xo.abs());
+                let xhat = DVector::from_fn(d, |i, _| 2. * self.xopt[i].signum() * x[i]);
+                let zhat = DVector::from_fn(d, |i, _| {
+                    if i == 0 {
+                        xhat[0]
+                    } else {
+                        xhat[i] + 0.25 * (xhat[i - 1] - abs2[i - 1])
+                    }
+                });
+                let z = ((zhat - &abs2).component_mul(&lambda(10., d)) + &abs2) * 100.;
+                let pen: f64 = z.iter().map(|zi| (zi.abs() - 500.).max(0.).powi(2)).sum();
+                let sum: f64 = z.iter().map(|zi| zi * zi.abs().sqrt().sin()).sum();
+                0.01 * (pen + 418.9828872724339 - sum / d as f64)
+            }
+            // Gallagher, 101 and 21 peaks
+            21 | 22 => {
+                let peaks = self.peaks.as_ref().unwrap();
+                let tmx = &self.rot2 * &x;
+                let best = (0..peaks.values.len())
+                    .map(|i| {
+                        let dist: f64 = (0..d)
+                            .map(|j| {
+                                peaks.scales[(i, j)] * (tmx[j] - peaks.x_local[(j, i)]).powi(2)
+                            })
+                            .sum();
+                        peaks.values[i] * (-0.5 / d as f64 * dist).exp()
+                    })
+                    .fold(0., f64::max);
+                let f = 10. - best;
+                let a = 0.1;
+                let f_true = if f > 0. {
+                    let t = f.ln() / a;
+                    (t + 0.49 * (t.sin() + (0.79 * t).sin())).exp().powf(a)
+                } else if f < 0. {
+                    let t = (-f).ln() / a;
+                    -(t + 0.49 * ((0.55 * t).sin() + (0.31 * t).sin()))
+                        .exp()
+                        .powf(a)
+                } else {
+                    f
+                };
+                f_true * f_true + f_pen(&x)
+            }
+            // Katsuura
+            23 => {
+                let z = self.conditioned(100.) * &shifted;
+                let prod: f64 = (0..d)
+                    .map(|i| {
+                        let sum: f64 = (1..33)
+                            .map(|j| {
+                                let p = 2f64.powi(j);
+                                (p * z[i] - (p * z[i] + 0.5).floor()).abs() / p
+                            })
+                            .sum();
+                        (1. + (i + 1) as f64 * sum).powf(10. / (d as f64).powf(1.2))
+                    })
+                    .product();
+                let d2 = (d * d) as f64;
+                10. / d2 * (prod - 1.) + f_pen(&x)
+            }
+            // Lunacek bi-Rastrigin
+            24 => {
+                let (mu0, dd) = (2.5, 1.);
+                let s = 1. - 0.5 / ((d as f64 + 20.).sqrt() - 4.1);
+                let mu1 = -((mu0 * mu0 - dd) / s).sqrt();
+                let xhat = DVector::from_fn(d, |i, _| 2. * self.xopt[i].signum() * x[i]);
+                let z = self.conditioned(100.) * xhat.map(|xi| xi - mu0);
+                let sum1: f64 = xhat.iter().map(|xi| (xi - mu0).powi(2)).sum();
+                let sum2: f64 = xhat.iter().map(|xi| (xi - mu1).powi(2)).sum();
+                let sum3: f64 = z.iter().map(|zi| (2. * PI * zi).cos()).sum();
+                sum1.min(dd * d as f64 + s * sum2) + 10. * (d as f64 - sum3) + 1e4 * f_pen(&x)
+            }
+            _ => unreachable!(),
+        };
+        raw + self.fopt
+    }
+}
+
+impl FitnessFunction for Bbob {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        pop.y
+            .row_iter()
+            .map(|row| {
+                let x: Vec<f64> = row.iter().map(|&v| v as f64).collect();
+                self.value(&x) as f32
+            })
+            .collect::<Vec<f32>>()
+            .into()
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.obj_dim
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        &self.dir
+    }
+}
+
+impl KnownOptimum for Bbob {
+    fn optimum_x(&self) -> DVector<f32> {
+        self.xopt.map(|x| x as f32)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        self.fopt as f32
+    }
+}
@@ -0,0 +1,316 @@
+use crate::bbob::Bbob;
+use crate::fitness::Fitness;
+use crate::params::{CmaesParams, CmaesParamsValidator};
+use crate::state::{CmaesState, CmaesStateLogic};
+use crate::strategy::{Budget, CmaesAlgo, CmaesAlgoOptimizer};
+use anyhow::{ensure, Result};
+use nalgebra::DVector;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Precisions `10^2` down to `10^-8`, five per decade as in COCO.
+pub fn default_targets() -> Vec<f64> {
+    (0..=50).map(|k| 10f64.powf(2. - k as f64 / 5.)).collect()
+}
+
+/// Set of BBOB functions, dimensions and seeds to run.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BenchmarkConfig {
+    pub functions: Vec<usize>,    // BBOB function ids, 1 to 24
+    pub dims: Vec<usize>,         // Problem dimensions
+    pub seeds: Vec<u64>,          // Random seeds, also used as BBOB instances
+    pub targets: Vec<f64>,        // Target precisions on f - fopt
+    pub budget_multiplier: usize, // Maximum evaluations per dimension
+    pub only_diag: bool,          // Whether to run the diagonal variant
+}
+
+impl Default for BenchmarkConfig {
+    fn default() -> Self {
+        Self {
+            functions: (1..=24).collect(),
+            dims: vec![2, 3, 5, 10],
+            seeds: (1..=5).collect(),
+            targets: default_targets(),
+            budget_multiplier: 1000,
+            only_diag: false,
+        }
+    }
+}
+
+/// Outcome of one run of CMA-ES.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TrialResult {
+    pub function: usize,
+    pub dim: usize,
+    pub seed: u64,
+    pub evals: usize,             // Evaluations used
+    pub best_delta: f64,          // Best f - fopt reached
+    pub hits: Vec<Option<usize>>, // Evaluations to reach each target, if reached
+}
+
+/// Expected running time to reach a target on a (function, dimension) pair.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ErtRow {
+    pub function: usize,
+    pub dim: usize,
+    pub target: f64,
+    pub successes: usize,
+    pub trials: usize,
+    pub ert: Option<f64>, // None when no trial reached the target
+}
+
+/// Fraction of (trial, target) pairs reached within a budget, per dimension.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EcdfRow {
+    pub dim: usize,
+    pub evals_per_dim: f64,
+    pub fraction: f64,
+}
+
+/// Results of a benchmark, comparable between crate versions.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BenchmarkReport {
+    pub version: String,
+    pub config: BenchmarkConfig,
+    pub trials: Vec<TrialResult>,
+    pub ert: Vec<ErtRow>,
+    pub ecdf: Vec<EcdfRow>,
+}
+
+/// Runs CMA-ES on one BBOB problem until the last target or the budget is reached.
+///
+/// The optimizer sees `f - fopt`, so the `f32` fitness does not lose
+/// precision to large optimal values.
+///
+/// ```rust
+/// use haru_cmaes::benchmark::{default_targets, run_trial};
+///
+/// let trial = run_trial(1, 2, 1, &default_targets(), 2000, false).unwrap();
+///
+/// // The sphere is solved to 1e-8 well within budget
+/// assert!(trial.best_delta <= 1e-8);
+/// assert!(trial.hits.iter().all(|h| h.is_some()));
+/// ```
+pub fn run_trial(
+    function: usize,
+    dim: usize,
+    seed: u64,
+    targets: &[f64],
+    budget: usize,
+    only_diag: bool,
+) -> Result<TrialResult> {
+    let mut problem = Bbob::new(function, dim, seed as usize)?;
+    problem.fopt = 0.0;
+    fastrand::seed(seed);
+
+    let popsize = 4 + (3. * (dim as f32).ln()) as i32;
+    let mut params = CmaesParams::new()
+        .and_then(|p| p.set_popsize(popsize))
+        .and_then(|p| p.set_xstart(dim, 0.0))
+        .and_then(|p| p.set_sigma(2.0))
+        .and_then(|p| p.set_only_diag(only_diag))?;
+    params.xstart = (0..dim).map(|_| 8. * fastrand::f32() - 4.).collect();
+    let cmaes = CmaesAlgo::new(params)?;
+    let mut state = CmaesState::init_state(&cmaes.params)?;
+
+    let min_target = targets.iter().cloned().fold(f64::INFINITY, f64::min);
+    let mut hits = vec![None; targets.len()];
+    let (mut evals, mut best_delta) = (0, f64::INFINITY);
+    let mut left = Budget::new(Some(budget as i32), None);
+    while left.exhausted().is_none() && best_delta > min_target {
+        let mut pop = cmaes.ask(&mut state)?;
+        let lam = left.candidates(pop.y.nrows());
+        pop.y = pop.y.rows(0, lam).into_owned();
+        left.spend(lam);
+        // Exact deltas, one evaluation at a time for the hitting times
+        let mut values = Vec::with_capacity(pop.y.nrows());
+        for row in pop.y.row_iter() {
+            let x: Vec<f64> = row.iter().map(|&v| v as f64).collect();
+            let delta = problem.value(&x);
+            evals += 1;
+            best_delta = best_delta.min(delta);
+            for (hit, &target) in hits.iter_mut().zip(targets) {
+                if hit.is_none() && best_delta <= target {
+                    *hit = Some(evals);
+                }
+            }
+            // Non-finite or beyond f32, rank worst
+            values.push(if (delta as f32).is_finite() {
+                delta as f32
+            } else {
+                f32::MAX
+            });
+        }
+        let mut fitness = Fitness {
+            values: DVector::from_vec(values),
+        };
+        state = cmaes.tell_truncated(state, &mut pop, &mut fitness)?;
+    }
+
+    Ok(TrialResult {
+        function,
+        dim,
+        seed,
+        evals,
+        best_delta,
+        hits,
+    })
+}
+
+/// Expected running times over the trials of each (function, dimension, target).
+///
+/// `ERT = (sum of evaluations spent until hit or budget) / (number of hits)`.
+pub fn ert(trials: &[TrialResult], targets: &[f64]) -> Vec<ErtRow> {
+    let mut groups: Vec<(usize, usize)> = trials.iter().map(|t| (t.function, t.dim)).collect();
+    groups.sort();
+    groups.dedup();
+    let mut rows = Vec::new();
+    for (function, dim) in groups {
+        let group: Vec<&TrialResult> = trials
+            .iter()
+            .filter(|t| t.function == function && t.dim == dim)
+            .collect();
+        for (k, &target) in targets.iter().enumerate() {
+            let successes = group.iter().filter(|t| t.hits[k].is_some()).count();
+            let spent: usize = group.iter().map(|t| t.hits[k].unwrap_or(t.evals)).sum();
+            rows.push(ErtRow {
+                function,
+                dim,
+                target,
+                successes,
+                trials: group.len(),
+                ert: (successes > 0).then(|| spent as f64 / successes as f64),
+            });
+        }
+    }
+    rows
+}
+
+/// Runtime distributions over all functions and targets of each dimension,
+/// at budgets `10^(k/5)` evaluations per dimension.
+pub fn ecdf(trials: &[TrialResult], budget_multiplier: usize) -> Vec<EcdfRow> {
+    let mut dims: Vec<usize> = trials.iter().map(|t| t.dim).collect();
+    dims.sort();
+    dims.dedup();
+    let max_k = (5. * (budget_multiplier.max(1) as f64).log10()).ceil() as usize;
+    let mut rows = Vec::new();
+    for dim in dims {
+        let hits: Vec<Option<usize>> = trials
+            .iter()
+            .filter(|t| t.dim == dim)
+            .flat_map(|t| t.hits.iter().cloned())
+            .collect();
+        for k in 0..=max_k {
+            let evals_per_dim = 10f64.powf(k as f64 / 5.);
+            let budget = evals_per_dim * dim as f64;
+            let reached = hits
+                .iter()
+                .filter(|h| h.is_some_and(|e| e as f64 <= budget))
+                .count();
+            rows.push(EcdfRow {
+                dim,
+                evals_per_dim,
+                fraction: reached as f64 / hits.len().max(1) as f64,
+            });
+        }
+    }
+    rows
+}
+
+/// Runs all trials of a configuration and summarizes them.
+///
+/// ```rust
+/// use haru_cmaes::benchmark::{run_benchmark, BenchmarkConfig};
+///
+/// let config = BenchmarkConfig {
+///     functions: vec![1, 2],
+///     dims: vec![2],
+///     seeds: vec![1, 2],
+///     budget_multiplier: 500,
+///     ..Default::default()
+/// };
+/// let report = run_benchmark(&config).unwrap();
+///
+/// assert_eq!(report.trials.len(), 4);
+/// assert_eq!(report.ert.len(), 2 * config.targets.len());
+/// // The easiest target is reached by every trial
+/// assert!(report.ert.iter().filter(|r| r.target == 100.0).all(|r| r.successes == 2));
+/// assert!(report.ecdf.windows(2).all(|w| w[0].fraction <= w[1].fraction));
+/// ```
+pub fn run_benchmark(config: &BenchmarkConfig) -> Result<BenchmarkReport> {
+    ensure!(
+        !config.targets.is_empty(),
+        "At least one target is required"
+    );
+    let mut trials = Vec::new();
+    for &dim in &config.dims {
+        for &function in &config.functions {
+            for &seed in &config.seeds {
+                trials.push(run_trial(
+                    function,
+                    dim,
+                    seed,
+                    &config.targets,
+                    config.budget_multiplier * dim,
+                    config.only_diag,
+                )?);
+            }
+        }
+    }
+
+    Ok(BenchmarkReport {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        config: config.clone(),
+        ert: ert(&trials, &config.targets),
+        ecdf: ecdf(&trials, config.budget_multiplier),
+        trials,
+    })
+}
+
+impl BenchmarkReport {
+    /// Writes `trials.csv`, `ert.csv` and `ecdf.csv` into `dir`, and
+    /// `report.json` with the `serde` feature.
+    pub fn write(&self, dir: impl AsRef<Path>) -> Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        #[cfg(feature = "serde")]
+        fs::write(dir.join("report.json"), serde_json::to_string_pretty(self)?)?;
+
+        let mut trials = String::from("function,dim,seed,evals,best_delta\n");
+        for t in &self.trials {
+            trials.push_str(&format!(
+                "{},{},{},{},{:e}\n",
+                t.function, t.dim, t.seed, t.evals, t.best_delta
+            ));
+        }
+        fs::write(dir.join("trials.csv"), trials)?;
+
+        let mut ert = String::from("function,dim,target,successes,trials,ert\n");
+        for r in &self.ert {
+            ert.push_str(&format!(
+                "{},{},{:e},{},{},{}\n",
+                r.function,
+                r.dim,
+                r.target,
+                r.successes,
+                r.trials,
+                r.ert.map_or("inf".to_string(), |e| e.to_string())
+            ));
+        }
+        fs::write(dir.join("ert.csv"), ert)?;
+
+        let mut ecdf = String::from("dim,evals_per_dim,fraction\n");
+        for r in &self.ecdf {
+            ecdf.push_str(&format!("{},{},{}\n", r.dim, r.evals_per_dim, r.fraction));
+        }
+        fs::write(dir.join("ecdf.csv"), ecdf)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use anyhow::{anyhow, bail, Context, Result};
+use haru_cmaes::benchmark::{run_benchmark, BenchmarkConfig};
+use std::env::args;
+use std::str::FromStr;
+
+const USAGE: &str = "Usage: haru-bench [--functions 1,2,...] [--dims 2,5,...] [--seeds 1,2,...] \
+[--budget EVALS_PER_DIM] [--only-diag] [--out DIR]";
+
+fn parse_list<T: FromStr>(value: Option<String>, flag: &str) -> Result<Vec<T>> {
+    value
+        .ok_or_else(|| anyhow!("Missing value for {}", flag))?
+        .split(',')
+        .map(|v| {
+            v.trim()
+                .parse()
+                .map_err(|_| anyhow!("Invalid value '{}' for {}", v, flag))
+        })
+        .collect()
+}
+
+fn main() -> Result<()> {
+    let mut config = BenchmarkConfig::default();
+    let mut out = String::from("bench_results");
+    let mut args = args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--functions" => config.functions = parse_list(args.next(), &arg)?,
+            "--dims" => config.dims = parse_list(args.next(), &arg)?,
+            "--seeds" => config.seeds = parse_list(args.next(), &arg)?,
+            "--budget" => {
+                config.budget_multiplier = args
+                    .next()
+                    .context("Missing value for --budget")?
+                    .parse()
+                    .context("Invalid value for --budget")?
+            }
+            "--only-diag" => config.only_diag = true,
+            "--out" => out = args.next().context("Missing value for --out")?,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => bail!("Unknown argument '{}'\n{}", arg, USAGE),
+        }
+    }
+
+    let report = run_benchmark(&config)?;
+    report.write(&out)?;
+
+    // Summary: ERT to the last target per function and dimension
+    let last = *config.targets.last().context("No targets")?;
+    println!("function  dim  successes  ERT({:.0e})", last);
+    for row in report.ert.iter().filter(|r| r.target == last) {
+        println!(
+            "{:>8}  {:>3}  {:>6}/{:<3}  {}",
+            row.function,
+            row.dim,
+            row.successes,
+            row.trials,
+            row.ert.map_or("inf".to_string(), |e| format!("{:.0}", e))
+        );
+    }
+    println!("Results written to {}", out);
+    Ok(())
+}
@@ -0,0 +1,553 @@
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use haru_cmaes::datalogger::CmaDataLogger;
+use haru_cmaes::distributed::Coordinator;
+use haru_cmaes::fitness::{Fitness, FitnessFunction, PopulationY};
+#[cfg(feature = "count-allocations")]
+use haru_cmaes::metrics::CountingAllocator;
+use haru_cmaes::metrics::{timed, Metrics, Phase};
+use haru_cmaes::objectives::by_name;
+use haru_cmaes::observer::Termination;
+use haru_cmaes::optimize::MIN_STEP;
+use haru_cmaes::params::{CmaesParams, CmaesParamsValidator, StepSizeRule};
+use haru_cmaes::plot::History;
+use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+use haru_cmaes::strategy::{Budget, CmaesAlgo, CmaesAlgoOptimizer};
+use nalgebra::{DMatrix, DVector};
+use serde::{Deserialize, Serialize};
+use std::env::args;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Counts every allocation of the process, so only with the `count-allocations` feature
+#[cfg(feature = "count-allocations")]
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const USAGE: &str =
+    "Usage: haru-cmaes [--config FILE] [--objective NAME | --command CMD] [--dim N] \
+[--popsize N] [--sigma S] [--xstart X1,X2,... | --origin X] [--gens N] [--max-evals N] \
+[--max-time SECONDS] [--tol T] \
+[--step-size csa|tpa|median] [--only-diag] [--maximize] [--seed N] [--every K] [--quiet] \
+[--checkpoint FILE] [--resume FILE] [--out FILE] [--log-dir DIR] [--plot FILE] [--metrics] \
+[--serve ADDR] [--timeout SECONDS]
+
+Objectives: sphere, ellipsoid, rosenbrock, rastrigin, ackley, griewank, levy, zakharov,
+schwefel12, schwefel226, styblinski-tang, bent-cigar, different-powers, sharp-ridge,
+michalewicz, bbob-F (BBOB function F, instance 1).
+
+An external command gets one candidate per run on stdin, as space separated
+values, and must print its objective value on stdout.
+
+With --serve, built-in objectives are evaluated by haru-worker processes
+connecting to ADDR (e.g. 0.0.0.0:7878); a candidate unanswered after --timeout
+seconds (default 60) is handed to another worker.
+
+The config file is JSON with the long flag names as keys, e.g.
+{\"objective\": \"rastrigin\", \"dim\": 5, \"popsize\": 40}. Flags override it.
+On Ctrl-C the state is written to the checkpoint file, to continue with --resume.
+--max-evals truncates the last generation to the evaluations left; --max-time is
+checked between generations.
+With --log-dir, pycma's outcmaes files are written there every K generations.
+With --plot, the diagnostic figure of the run is written as SVG.
+With --metrics, phase timings are summarized on stderr, with allocation counts and
+peak memory when built with the count-allocations feature.";
+
+/// Run settings, from a JSON config file and flags.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    objective: String,       // Built-in objective
+    command: Option<String>, // External objective command, replaces `objective`
+    dim: usize,
+    popsize: Option<i32>, // Defaults to 4 + 3 ln(dim)
+    sigma: f32,
+    xstart: Option<Vec<f32>>, // Defaults to `origin` in every coordinate
+    origin: f32,
+    gens: i32,
+    max_evals: Option<i32>, // Evaluations over the whole run, resumed parts included
+    max_time: Option<f64>,  // Seconds of this invocation
+    tol: f32,
+    step_size: String,
+    only_diag: bool,
+    maximize: bool,
+    seed: Option<u64>,
+    every: i32, // Generations between progress lines
+    quiet: bool,
+    checkpoint: String,
+    out: Option<String>,     // Result file, besides stdout
+    log_dir: Option<String>, // Directory of pycma data files
+    plot: Option<String>,    // SVG diagnostic figure
+    metrics: bool,           // Summarize timings and memory use
+    serve: Option<String>,   // Address workers connect to, evaluating remotely
+    timeout: f64,            // Seconds before a worker's candidate is re-dispatched
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            objective: "sphere".to_string(),
+            command: None,
+            dim: 5,
+            popsize: None,
+            sigma: 1.0,
+            xstart: None,
+            origin: 0.0,
+            gens: 1000,
+            max_evals: None,
+            max_time: None,
+            tol: 1e-12,
+            step_size: "csa".to_string(),
+            only_diag: false,
+            maximize: false,
+            seed: None,
+            every: 10,
+            quiet: false,
+            checkpoint: "haru-cmaes-checkpoint.json".to_string(),
+            out: None,
+            log_dir: None,
+            plot: None,
+            metrics: false,
+            serve: None,
+            timeout: 60.0,
+        }
+    }
+}
+
+/// Everything needed to continue an interrupted run.
+#[derive(Debug, Serialize, Deserialize)]
+struct Checkpoint {
+    config: Config,
+    rng_seed: u64,
+    g: i32,
+    evals_count: i32,
+    mean: Vec<f32>,
+    sigma: f32,
+    cov: Vec<Vec<f32>>,
+    ps: Vec<f32>,
+    pc: Vec<f32>,
+    best_y: Vec<f32>,
+    best_y_fit: f32,
+    best_y_hist: Vec<f32>,
+    prev_mean: Vec<f32>,
+    prev_fit: Vec<f32>,
+    step_size_s: f32,
+}
+
+impl Checkpoint {
+    fn new(config: &Config, state: &CmaesState) -> Self {
+        Self {
+            config: config.clone(),
+            rng_seed: fastrand::get_seed(),
+            g: state.g,
+            evals_count: state.evals_count,
+            mean: state.mean.iter().copied().collect(),
+            sigma: state.sigma,
+            cov: state
+                .cov
+                .row_iter()
+                .map(|r| r.iter().copied().collect())
+                .collect(),
+            ps: state.ps.iter().copied().collect(),
+            pc: state.pc.iter().copied().collect(),
+            best_y: state.best_y.iter().copied().collect(),
+            best_y_fit: state.best_y_fit[0],
+            best_y_hist: state.best_y_hist.clone(),
+            prev_mean: state.prev_mean.iter().copied().collect(),
+            prev_fit: state.prev_fit.iter().copied().collect(),
+            step_size_s: state.step_size_s,
+        }
+    }
+
+    /// Overwrites the search distribution and history of `state`.
+    fn restore(&self, state: &mut CmaesState) -> Result<()> {
+        let n = state.mean.len();
+        ensure!(
+            self.mean.len() == n && self.cov.len() == n,
+            "Checkpoint has dimension {}, expected {}",
+            self.mean.len(),
+            n
+        );
+        fastrand::seed(self.rng_seed);
+        state.g = self.g;
+        state.evals_count = self.evals_count;
+        state.mean = DVector::from_vec(self.mean.clone());
+        state.sigma = self.sigma;
+        state.cov = DMatrix::from_row_iterator(n, n, self.cov.iter().flatten().copied());
+        state.ps = DVector::from_vec(self.ps.clone());
+        state.pc = DVector::from_vec(self.pc.clone());
+        state.best_y = DVector::from_vec(self.best_y.clone());
+        state.best_y_fit = DVector::from_element(1, self.best_y_fit);
+        state.best_y_hist = self.best_y_hist.clone();
+        state.prev_mean = DVector::from_vec(self.prev_mean.clone());
+        state.prev_fit = DVector::from_vec(self.prev_fit.clone());
+        state.step_size_s = self.step_size_s;
+        Ok(())
+    }
+}
+
+/// Final output, printed as JSON.
+#[derive(Debug, Serialize)]
+struct RunResult {
+    status: String, // "converged", "max_generations", "max_evaluations", "max_time" or "interrupted"
+    objective: String,
+    best_x: Vec<f32>,
+    best_f: f32,
+    generations: i32,
+    evaluations: i32,
+    sigma: f32,
+    mean: Vec<f32>,
+    remaining_evals: Option<i32>,
+    remaining_time: Option<f64>, // Seconds
+    checkpoint: Option<String>,
+}
+
+/// Source of objective values, minimized after the sign flip for maximization.
+enum Objective {
+    BuiltIn(Box<dyn FitnessFunction>),
+    External(String),
+    Remote(Arc<Coordinator>),
+}
+
+impl Objective {
+    fn new(config: &Config) -> Result<Self> {
+        ensure!(
+            config.command.is_none() || config.serve.is_none(),
+            "--serve hands out built-in objectives, not --command"
+        );
+        if let Some(command) = &config.command {
+            return Ok(Objective::External(command.clone()));
+        }
+        let f = by_name(&config.objective, config.dim).map_err(|e| anyhow!("{}\n{}", e, USAGE))?;
+        let Some(addr) = &config.serve else {
+            return Ok(Objective::BuiltIn(f));
+        };
+        ensure!(config.timeout > 0.0, "Worker timeout must be positive");
+        let timeout = Duration::from_secs_f64(config.timeout);
+        let coordinator = Coordinator::bind(addr.as_str(), &config.objective, timeout)?;
+        if !config.quiet {
+            eprintln!(
+                "Serving {} to workers on {}",
+                config.objective,
+                coordinator.local_addr()
+            );
+        }
+        Ok(Objective::Remote(Arc::new(coordinator)))
+    }
+
+    /// Raw objective values of the population, in the user's sign.
+    fn values(&self, pop: &PopulationY) -> Result<DVector<f32>> {
+        match self {
+            Objective::BuiltIn(f) => Ok(f.cost(pop)),
+            Objective::External(command) => pop
+                .y
+                .row_iter()
+                .map(|row| {
+                    let x: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+                    run_command(command, &x.join(" "))
+                })
+                .collect::<Result<Vec<f32>>>()
+                .map(DVector::from_vec),
+            Objective::Remote(coordinator) => coordinator.evaluate(pop),
+        }
+    }
+}
+
+/// Runs `command` in a shell with `input` on stdin and parses its output.
+fn run_command(command: &str, input: &str) -> Result<f32> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Cannot run '{}'", command))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "{}", input)?;
+    }
+    let output = child.wait_with_output()?;
+    ensure!(
+        output.status.success(),
+        "'{}' failed with {} on input {}",
+        command,
+        output.status,
+        input
+    );
+    let text = String::from_utf8_lossy(&output.stdout);
+    text.trim()
+        .parse()
+        .map_err(|_| anyhow!("'{}' printed '{}', expected a number", command, text.trim()))
+}
+
+fn parse<T: FromStr>(value: Option<String>, flag: &str) -> Result<T> {
+    let value = value.ok_or_else(|| anyhow!("Missing value for {}", flag))?;
+    value
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("Invalid value '{}' for {}", value, flag))
+}
+
+fn parse_list<T: FromStr>(value: Option<String>, flag: &str) -> Result<Vec<T>> {
+    value
+        .ok_or_else(|| anyhow!("Missing value for {}", flag))?
+        .split(',')
+        .map(|v| parse(Some(v.to_string()), flag))
+        .collect()
+}
+
+fn step_size_rule(name: &str) -> Result<StepSizeRule> {
+    match name {
+        "csa" => Ok(StepSizeRule::Csa),
+        "tpa" => Ok(StepSizeRule::Tpa),
+        "median" => Ok(StepSizeRule::MedianSuccess),
+        _ => bail!("Unknown step-size rule '{}', use csa, tpa or median", name),
+    }
+}
+
+/// Reads the config file and checkpoint, then applies the flags on top.
+fn parse_args() -> Result<Option<(Config, Option<Checkpoint>)>> {
+    let argv: Vec<String> = args().skip(1).collect();
+    let value_of = |flag: &str| {
+        argv.iter()
+            .position(|a| a == flag)
+            .map(|i| argv.get(i + 1).cloned())
+    };
+    let checkpoint: Option<Checkpoint> = match value_of("--resume") {
+        Some(path) => {
+            let path = path.context("Missing value for --resume")?;
+            let text =
+                fs::read_to_string(&path).with_context(|| format!("Cannot read {}", path))?;
+            Some(
+                serde_json::from_str(&text)
+                    .with_context(|| format!("Invalid checkpoint {}", path))?,
+            )
+        }
+        None => None,
+    };
+    let mut config = match (&checkpoint, value_of("--config")) {
+        (Some(c), _) => c.config.clone(),
+        (None, Some(path)) => {
+            let path = path.context("Missing value for --config")?;
+            let text =
+                fs::read_to_string(&path).with_context(|| format!("Cannot read {}", path))?;
+            serde_json::from_str(&text).with_context(|| format!("Invalid config {}", path))?
+        }
+        (None, None) => Config::default(),
+    };
+
+    let mut args = argv.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" | "--resume" => {
+                args.next();
+            }
+            "--objective" => config.objective = parse(args.next(), &arg)?,
+            "--command" => config.command = Some(parse(args.next(), &arg)?),
+            "--dim" => config.dim = parse(args.next(), &arg)?,
+            "--popsize" => config.popsize = Some(parse(args.next(), &arg)?),
+            "--sigma" => config.sigma = parse(args.next(), &arg)?,
+            "--xstart" => config.xstart = Some(parse_list(args.next(), &arg)?),
+            "--origin" => config.origin = parse(args.next(), &arg)?,
+            "--gens" => config.gens = parse(args.next(), &arg)?,
+            "--max-evals" => config.max_evals = Some(parse(args.next(), &arg)?),
+            "--max-time" => config.max_time = Some(parse(args.next(), &arg)?),
+            "--tol" => config.tol = parse(args.next(), &arg)?,
+            "--step-size" => config.step_size = parse(args.next(), &arg)?,
+            "--only-diag" => config.only_diag = true,
+            "--maximize" => config.maximize = true,
+            "--seed" => config.seed = Some(parse(args.next(), &arg)?),
+            "--every" => config.every = parse(args.next(), &arg)?,
+            "--quiet" => config.quiet = true,
+            "--checkpoint" => config.checkpoint = parse(args.next(), &arg)?,
+            "--out" => config.out = Some(parse(args.next(), &arg)?),
+            "--log-dir" => config.log_dir = Some(parse(args.next(), &arg)?),
+            "--plot" => config.plot = Some(parse(args.next(), &arg)?),
+            "--metrics" => config.metrics = true,
+            "--serve" => config.serve = Some(parse(args.next(), &arg)?),
+            "--timeout" => config.timeout = parse(args.next(), &arg)?,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(None);
+            }
+            _ => bail!("Unknown argument '{}'\n{}", arg, USAGE),
+        }
+    }
+    if let Some(xstart) = &config.xstart {
+        config.dim = xstart.len();
+    }
+    Ok(Some((config, checkpoint)))
+}
+
+fn params(config: &Config) -> Result<CmaesParams> {
+    ensure!(config.dim >= 1, "Dimension must be at least 1");
+    ensure!(config.sigma > 0.0, "Sigma must be positive");
+    let popsize = config
+        .popsize
+        .unwrap_or(4 + (3. * (config.dim as f32).ln()) as i32);
+    ensure!(popsize >= 2, "Population size must be at least 2");
+    let mut params = CmaesParams::new()
+        .and_then(|p| p.set_popsize(popsize))
+        .and_then(|p| p.set_xstart(config.dim, config.origin))
+        .and_then(|p| p.set_sigma(config.sigma))
+        .and_then(|p| p.set_num_gens(config.gens))
+        .and_then(|p| p.set_tol(config.tol))
+        .and_then(|p| p.set_only_diag(config.only_diag))
+        .and_then(|p| p.set_step_size_rule(step_size_rule(&config.step_size)?))?;
+    if let Some(xstart) = &config.xstart {
+        params.xstart = xstart.clone();
+    }
+    Ok(params)
+}
+
+fn main() -> Result<()> {
+    let Some((config, checkpoint)) = parse_args()? else {
+        return Ok(());
+    };
+    if let Some(seed) = config.seed {
+        fastrand::seed(seed);
+    }
+    let objective = Objective::new(&config)?;
+    let sign = if config.maximize { -1.0 } else { 1.0 };
+    let cmaes = CmaesAlgo::new(params(&config)?)?;
+    let mut state = CmaesState::init_state(&cmaes.params)?;
+    if let Some(checkpoint) = &checkpoint {
+        checkpoint.restore(&mut state)?;
+    }
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = interrupted.clone();
+    let remote = match &objective {
+        Objective::Remote(coordinator) => Some(coordinator.clone()),
+        _ => None,
+    };
+    ctrlc::set_handler(move || {
+        flag.store(true, Ordering::SeqCst);
+        // Unblocks a generation waiting for workers
+        if let Some(coordinator) = &remote {
+            coordinator.close();
+        }
+    })?;
+
+    let mut logger = match &config.log_dir {
+        Some(dir) => Some(CmaDataLogger::new(dir, config.every.max(1))?),
+        None => None,
+    };
+
+    let mut history = History::default();
+    let mut metrics = Metrics::new();
+
+    if let Some(time) = config.max_time {
+        ensure!(time > 0.0, "Time budget must be positive");
+    }
+    let mut budget = Budget::new(
+        config.max_evals.map(|m| m - state.evals_count),
+        config.max_time.map(Duration::from_secs_f64),
+    );
+
+    let mut status = "max_generations";
+    while state.g < config.gens {
+        if let Some(spent) = budget.exhausted() {
+            status = match spent {
+                Termination::MaxTime => "max_time",
+                _ => "max_evaluations",
+            };
+            break;
+        }
+        let mut pop = cmaes.ask(&mut state)?;
+        let lam = budget.candidates(pop.y.nrows());
+        pop.y = pop.y.rows(0, lam).into_owned();
+        let values = match timed(Phase::Evaluation, || objective.values(&pop)) {
+            // Ctrl-C also reaches a running external command or the coordinator
+            Err(_) if interrupted.load(Ordering::SeqCst) => {
+                status = "interrupted";
+                break;
+            }
+            values => values?,
+        };
+        ensure!(
+            !values.iter().any(|v| v.is_nan()),
+            "Objective returned NaN at generation {}",
+            state.g + 1
+        );
+        budget.spend(lam);
+        let mut fitness = Fitness {
+            values: values * sign,
+        };
+        state = cmaes.tell_truncated(state, &mut pop, &mut fitness)?;
+        if let Some(logger) = logger.as_mut() {
+            logger.add(&state, &pop, &fitness)?;
+        }
+        if config.plot.is_some() {
+            history.record(&state, &fitness);
+        }
+        if config.metrics {
+            metrics.record(&state);
+        }
+
+        if !config.quiet && (state.g % config.every.max(1) == 0 || state.g == 1) {
+            eprintln!(
+                "gen {:>6}  evals {:>8}  best {:>14.6e}  sigma {:.3e}",
+                state.g,
+                state.evals_count,
+                sign * state.best_y_fit[0],
+                state.sigma
+            );
+        }
+        // Fitness stalled, or steps below f32 resolution
+        let max_step = state.sigma * state.eig_vals.max().sqrt();
+        if cmaes.is_done(&state, state.g)? || max_step < MIN_STEP {
+            status = "converged";
+            break;
+        }
+        if interrupted.load(Ordering::SeqCst) {
+            status = "interrupted";
+            break;
+        }
+    }
+
+    let mut saved = None;
+    if status == "interrupted" {
+        let text = serde_json::to_string_pretty(&Checkpoint::new(&config, &state))?;
+        fs::write(&config.checkpoint, text)
+            .with_context(|| format!("Cannot write checkpoint {}", config.checkpoint))?;
+        eprintln!(
+            "Interrupted, state saved to {}, continue with --resume {}",
+            config.checkpoint, config.checkpoint
+        );
+        saved = Some(config.checkpoint.clone());
+    }
+
+    if let Some(path) = &config.plot {
+        history.write_svg(path)?;
+    }
+    if config.metrics {
+        eprintln!("{}", metrics.summary());
+    }
+
+    let result = RunResult {
+        status: status.to_string(),
+        objective: config
+            .command
+            .clone()
+            .unwrap_or_else(|| config.objective.clone()),
+        best_x: state.best_y.iter().copied().collect(),
+        best_f: sign * state.best_y_fit[0],
+        generations: state.g,
+        evaluations: state.evals_count,
+        sigma: state.sigma,
+        mean: state.mean.iter().copied().collect(),
+        remaining_evals: budget.evals,
+        remaining_time: budget.remaining_time().map(|t| t.as_secs_f64()),
+        checkpoint: saved,
+    };
+    let json = serde_json::to_string_pretty(&result)?;
+    if let Some(out) = &config.out {
+        fs::write(out, &json).with_context(|| format!("Cannot write {}", out))?;
+    }
+    println!("{}", json);
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use anyhow::{anyhow, bail, Result};
+use haru_cmaes::distributed::Worker;
+use std::env::args;
+use std::time::Duration;
+
+const USAGE: &str = "Usage: haru-worker --connect HOST:PORT [--retry SECONDS]
+
+Evaluates candidates served by `haru-cmaes --serve HOST:PORT` with the built-in
+objectives, until the coordinator is done. With --retry, keeps trying to connect
+for that long, so workers may start before the coordinator.";
+
+fn main() -> Result<()> {
+    let mut addr = None;
+    let mut worker = Worker::with_builtins();
+    let mut args = args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--connect" => addr = args.next(),
+            "--retry" => {
+                let secs: f64 = args
+                    .next()
+                    .and_then(|v| v.trim().parse().ok())
+                    .filter(|s: &f64| s.is_finite() && *s >= 0.0)
+                    .ok_or_else(|| anyhow!("Invalid value for --retry"))?;
+                worker.retry = Duration::from_secs_f64(secs);
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => bail!("Unknown argument '{}'\n{}", arg, USAGE),
+        }
+    }
+    let addr = addr.ok_or_else(|| anyhow!("Missing --connect\n{}", USAGE))?;
+
+    let evaluations = worker.run(addr.as_str())?;
+    eprintln!("{} evaluations", evaluations);
+    Ok(())
+}
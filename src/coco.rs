@@ -0,0 +1,274 @@
+use crate::bbob::Bbob;
+use crate::fitness::{FitnessFunction, MinOrMax, PopulationY};
+use anyhow::{anyhow, Result};
+use nalgebra::DVector;
+use std::cell::RefCell;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Number of `.dat` targets per decade of `f - fopt`.
+const TARGETS_PER_DECADE: f64 = 5.;
+/// Number of `.tdat` triggers per decade of evaluations.
+const EVALS_PER_DECADE: f64 = 20.;
+
+/// Formats like C's `%+.{prec}e`, i.e. with a signed two-digit exponent.
+fn sci(x: f64, prec: usize) -> String {
+    let s = format!("{:+.*e}", prec, x);
+    match s.split_once('e') {
+        Some((mantissa, exp)) => {
+            let exp: i32 = exp.parse().unwrap_or(0);
+            let sign = if exp < 0 { '-' } else { '+' };
+            format!("{}e{}{:02}", mantissa, sign, exp.abs())
+        }
+        None => s, // inf and NaN
+    }
+}
+
+/// Running record of one (function, dimension, instance) trial.
+struct Trial {
+    evals: usize,
+    best_delta: f64,         // Best f - fopt so far
+    best_f: f64,             // Best measured f so far
+    target_idx: Option<i64>, // Last target passed, on a 10^(1/5) grid
+    trigger_idx: i64,        // Next evaluation trigger, on a 10^(1/20) grid
+    last_line: String,       // Last evaluation, written on close
+    dat_evals: usize,        // Last evaluation written to .dat
+    tdat_evals: usize,       // Last evaluation written to .tdat
+    dat: BufWriter<File>,
+    tdat: BufWriter<File>,
+    error: Option<std::io::Error>,
+}
+
+/// COCO experiment logger for the BBOB functions.
+///
+/// Wraps a `Bbob` problem and records every evaluation in the legacy
+/// format read by COCO's post-processing (`cocopp`):
+/// - `bbobexp_f{f}.info` indexes the trials of function `f`, with one header
+///   per dimension and all its instances listed on the line that follows,
+/// - `data_f{f}/bbobexp_f{f}_DIM{d}.dat` gets a line each time the best
+///   `f - fopt` passes a target `10^(k/5)`,
+/// - `data_f{f}/bbobexp_f{f}_DIM{d}.tdat` gets a line at evaluation counts
+///   `10^(k/20)`.
+///
+/// The logger is the objective handed to `evaluate` between `ask` and
+/// `tell`. Differences to `fopt` are computed in `f64`, so precisions down
+/// to `1e-8` are logged even though fitness values are `f32`. Call `close`
+/// at the end of the trial to write the last evaluation and the index entry.
+///
+/// ```rust
+/// use haru_cmaes::bbob::Bbob;
+/// use haru_cmaes::coco::CocoLogger;
+/// use haru_cmaes::fitness::FitnessEvaluator;
+/// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+/// use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+/// use haru_cmaes::strategy::{CmaesAlgo, CmaesAlgoOptimizer};
+///
+/// let root = std::env::temp_dir().join("haru_cmaes_coco_doc");
+/// let _ = std::fs::remove_dir_all(&root);
+/// let problem = Bbob::new(1, 2, 1).unwrap();
+/// let logger = CocoLogger::new(&root, "haru_cmaes", problem).unwrap();
+///
+/// let params = CmaesParams::new()
+///     .and_then(|p| p.set_xstart(2, 0.0))
+///     .and_then(|p| p.set_sigma(2.0))
+///     .unwrap();
+/// let cmaes = CmaesAlgo::new(params).unwrap();
+/// let mut state = CmaesState::init_state(&cmaes.params).unwrap();
+/// for _ in 0..20 {
+///     let mut pop = cmaes.ask(&mut state).unwrap();
+///     let mut fitness = logger.evaluate(&pop).unwrap();
+///     state = cmaes.tell(state, &mut pop, &mut fitness).unwrap();
+/// }
+/// let evals = logger.evals();
+/// logger.close().unwrap();
+///
+/// // A second instance goes on the same line of the index
+/// let logger = CocoLogger::new(&root, "haru_cmaes", Bbob::new(1, 2, 2).unwrap()).unwrap();
+/// let pop = cmaes.ask(&mut state).unwrap();
+/// logger.evaluate(&pop).unwrap();
+/// logger.close().unwrap();
+///
+/// let info = std::fs::read_to_string(root.join("bbobexp_f1.info")).unwrap();
+/// assert!(info.starts_with("funcId = 1, DIM = 2, Precision = 1.000e-08, algId = 'haru_cmaes'"));
+/// assert_eq!(info.matches("funcId").count(), 1);
+/// let line = info.lines().nth(2).unwrap();
+/// assert!(line.starts_with(&format!("data_f1/bbobexp_f1_DIM2.dat, 1:{}|", evals)));
+/// assert!(line.contains(&format!(", 2:{}|", pop.y.nrows())));
+/// let dat = std::fs::read_to_string(root.join("data_f1/bbobexp_f1_DIM2.dat")).unwrap();
+/// assert!(dat.lines().nth(1).unwrap().starts_with("1 "));
+/// ```
+pub struct CocoLogger {
+    pub problem: Bbob,
+    pub alg_id: String,
+    pub root: PathBuf,
+    trial: RefCell<Trial>,
+}
+
+impl CocoLogger {
+    /// Opens the data files of `problem` under `root`, appending to previous trials.
+    pub fn new(root: impl AsRef<Path>, alg_id: &str, problem: Bbob) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        let (f, d) = (problem.function, problem.obj_dim);
+        let data_dir = root.join(format!("data_f{}", f));
+        fs::create_dir_all(&data_dir)?;
+        let open = |ext: &str| -> Result<BufWriter<File>> {
+            let path = data_dir.join(format!("bbobexp_f{}_DIM{}.{}", f, d, ext));
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            Ok(BufWriter::new(file))
+        };
+        let (mut dat, mut tdat) = (open("dat")?, open("tdat")?);
+        let header = format!(
+            "% function evaluation | noise-free fitness - Fopt ({}) | best noise-free fitness - Fopt | measured fitness | best measured fitness | x1 | x2...",
+            sci(problem.fopt, 12)
+        );
+        writeln!(dat, "{}", header)?;
+        writeln!(tdat, "{}", header)?;
+
+        Ok(Self {
+            problem,
+            alg_id: alg_id.to_string(),
+            root,
+            trial: RefCell::new(Trial {
+                evals: 0,
+                best_delta: f64::INFINITY,
+                best_f: f64::INFINITY,
+                target_idx: None,
+                trigger_idx: 0,
+                last_line: String::new(),
+                dat_evals: 0,
+                tdat_evals: 0,
+                dat,
+                tdat,
+                error: None,
+            }),
+        })
+    }
+
+    /// Number of evaluations logged so far.
+    pub fn evals(&self) -> usize {
+        self.trial.borrow().evals
+    }
+
+    /// Best `f - fopt` logged so far.
+    pub fn best_delta(&self) -> f64 {
+        self.trial.borrow().best_delta
+    }
+
+    /// Logs a single evaluation and returns its value.
+    fn record(&self, x: &[f64]) -> f64 {
+        let f = self.problem.value(x);
+        let delta = f - self.problem.fopt;
+        let mut trial = self.trial.borrow_mut();
+        trial.evals += 1;
+        trial.best_delta = trial.best_delta.min(delta);
+        trial.best_f = trial.best_f.min(f);
+        let xs: Vec<String> = x.iter().map(|xi| sci(*xi, 4)).collect();
+        trial.last_line = format!(
+            "{} {} {} {} {} {}",
+            trial.evals,
+            sci(delta, 9),
+            sci(trial.best_delta, 9),
+            sci(f, 9),
+            sci(trial.best_f, 9),
+            xs.join(" ")
+        );
+
+        // Target passed, or first evaluation
+        let idx = (TARGETS_PER_DECADE * trial.best_delta.max(1e-300).log10()).floor() as i64;
+        let write_dat = trial.target_idx.is_none_or(|last| idx < last);
+        if write_dat {
+            trial.target_idx = Some(idx);
+        }
+
+        // Evaluation trigger reached
+        let mut write_tdat = false;
+        while trial.evals as f64 >= 10f64.powf(trial.trigger_idx as f64 / EVALS_PER_DECADE) {
+            trial.trigger_idx += 1;
+            write_tdat = true;
+        }
+
+        let line = trial.last_line.clone();
+        let mut result = Ok(());
+        if write_dat {
+            result = result.and(writeln!(trial.dat, "{}", line));
+            trial.dat_evals = trial.evals;
+        }
+        if write_tdat {
+            result = result.and(writeln!(trial.tdat, "{}", line));
+            trial.tdat_evals = trial.evals;
+        }
+        if let Err(e) = result {
+            trial.error.get_or_insert(e);
+        }
+        f
+    }
+
+    /// Writes the last evaluation and adds the trial to the `.info` index.
+    pub fn close(self) -> Result<()> {
+        let mut trial = self.trial.into_inner();
+        if let Some(e) = trial.error.take() {
+            return Err(anyhow!("Failed to write COCO data: {}", e));
+        }
+        if trial.evals > 0 && trial.dat_evals < trial.evals {
+            writeln!(trial.dat, "{}", trial.last_line)?;
+        }
+        if trial.evals > 0 && trial.tdat_evals < trial.evals {
+            writeln!(trial.tdat, "{}", trial.last_line)?;
+        }
+        trial.dat.flush()?;
+        trial.tdat.flush()?;
+
+        // Instances of a function and dimension share one header and data line
+        let (f, d) = (self.problem.function, self.problem.obj_dim);
+        let path = self.root.join(format!("bbobexp_f{}.info", f));
+        let header = format!(
+            "funcId = {}, DIM = {}, Precision = 1.000e-08, algId = '{}'",
+            f, d, self.alg_id
+        );
+        let entry = format!(
+            ", {}:{}|{}",
+            self.problem.instance,
+            trial.evals,
+            sci(trial.best_delta, 1).trim_start_matches('+')
+        );
+        let mut lines: Vec<String> = match fs::read_to_string(&path) {
+            Ok(text) => text.lines().map(str::to_string).collect(),
+            Err(_) => Vec::new(),
+        };
+        match lines.iter().position(|l| *l == header) {
+            Some(i) if i + 2 < lines.len() => lines[i + 2].push_str(&entry),
+            _ => {
+                lines.push(header);
+                lines.push("%".to_string());
+                lines.push(format!("data_f{}/bbobexp_f{}_DIM{}.dat{}", f, f, d, entry));
+            }
+        }
+        fs::write(&path, lines.join("\n") + "\n")?;
+        Ok(())
+    }
+}
+
+impl FitnessFunction for CocoLogger {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        pop.y
+            .row_iter()
+            .map(|row| {
+                let x: Vec<f64> = row.iter().map(|&v| v as f64).collect();
+                self.record(&x) as f32
+            })
+            .collect::<Vec<f32>>()
+            .into()
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.problem.obj_dim
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        &self.problem.dir
+    }
+}
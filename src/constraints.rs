@@ -0,0 +1,237 @@
+use crate::fitness::{Fitness, FitnessEvaluator, FitnessFunction, PopulationY};
+use crate::state::CmaesState;
+use crate::strategy::{CmaesAlgo, CmaesAlgoOptimizer};
+use anyhow::{ensure, Result};
+use nalgebra::{DMatrix, DVector, RowDVector};
+
+/// A trait for constraint functions.
+///
+/// Both methods return one row per individual and one column per constraint.
+/// Inequalities are satisfied when `g(x) <= 0`, equalities when `h(x) = 0`.
+pub trait ConstraintFunction {
+    fn inequality(&self, pop: &PopulationY) -> DMatrix<f32>;
+    fn equality(&self, pop: &PopulationY) -> DMatrix<f32> {
+        DMatrix::zeros(pop.y.nrows(), 0)
+    }
+
+    /// Total violation per individual, zero when feasible.
+    fn violation(&self, pop: &PopulationY) -> DVector<f32> {
+        let ineq = self.inequality(pop).map(|g| g.max(0.0));
+        let eq = self.equality(pop).abs();
+        DVector::from_iterator(
+            pop.y.nrows(),
+            (0..pop.y.nrows()).map(|i| ineq.row(i).sum() + eq.row(i).sum()),
+        )
+    }
+}
+
+/// Augmented Lagrangian constraint handler.
+///
+/// Follows Atamna, Auger and Hansen (2016, 2017): the fitness of each
+/// individual is replaced by
+/// `f + sum_i (gamma_i g_i + omega_i / 2 g_i^2)` (or `-gamma_i^2 / (2 omega_i)`
+/// for inactive inequalities), and after every `tell` the Lagrange
+/// multipliers `gamma` and penalty coefficients `omega` are adapted from
+/// `f` and the constraints evaluated at the new mean. Multipliers move by
+/// `omega g / d_gamma`.
+///
+/// The optimum is `best_feasible`, ranked by raw fitness. `tell` only sees
+/// augmented fitness, so `state.best_y` and `state.best_y_fit` hold the best
+/// augmented candidate, which may be infeasible.
+///
+/// ```rust
+/// use haru_cmaes::constraints::{AugmentedLagrangian, ConstraintFunction};
+/// use haru_cmaes::fitness::{FitnessFunction, MinOrMax, PopulationY};
+/// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+/// use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+/// use haru_cmaes::strategy::CmaesAlgo;
+/// use nalgebra::{DMatrix, DVector};
+///
+/// // Sphere subject to x_0 >= 1, i.e. 1 - x_0 <= 0
+/// struct Sphere;
+///
+/// impl FitnessFunction for Sphere {
+///     fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+///         pop.y.row_iter().map(|row| row.norm_squared()).collect::<Vec<f32>>().into()
+///     }
+///     fn cost_dim(&self) -> usize { 3 }
+///     fn optimization_type(&self) -> &MinOrMax { &MinOrMax::Min }
+/// }
+///
+/// impl ConstraintFunction for Sphere {
+///     fn inequality(&self, pop: &PopulationY) -> DMatrix<f32> {
+///         DMatrix::from_fn(pop.y.nrows(), 1, |i, _| 1.0 - pop.y[(i, 0)])
+///     }
+/// }
+///
+/// fastrand::seed(7);
+/// let params = CmaesParams::new()
+///     .and_then(|p| p.set_xstart(3, 2.0))
+///     .and_then(|p| p.set_sigma(0.5))
+///     .unwrap();
+/// let cmaes = CmaesAlgo::new(params).unwrap();
+/// let mut state = CmaesState::init_state(&cmaes.params).unwrap();
+/// let mut al = AugmentedLagrangian::new(3, 1, 0).unwrap();
+/// for _ in 0..60 {
+///     state = al.step(&cmaes, state, &Sphere, &Sphere).unwrap();
+/// }
+/// let (x, f) = al.best_feasible.clone().unwrap();
+///
+/// assert!(x[0] >= 1.0);
+/// assert!(f < 1.1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct AugmentedLagrangian {
+    pub gamma_ineq: DVector<f32>, // Lagrange multipliers of inequalities
+    pub omega_ineq: DVector<f32>, // Penalty coefficients of inequalities
+    pub gamma_eq: DVector<f32>,   // Lagrange multipliers of equalities
+    pub omega_eq: DVector<f32>,   // Penalty coefficients of equalities
+    pub chi: f32,                 // Penalty coefficient change factor
+    pub k1: f32,                  // Threshold on the change of the augmented fitness
+    pub k2: f32,                  // Threshold on the change of the constraints
+    pub d_gamma: f32,             // Damping of the multiplier update
+    pub n: f32,                   // Dimension of the problem space
+    pub best_feasible: Option<(DVector<f32>, f32)>, // Best feasible candidate and fitness
+    prev_mean: Option<(f32, RowDVector<f32>, RowDVector<f32>)>, // f, g and h at the previous mean
+}
+
+impl AugmentedLagrangian {
+    /// Creates a handler for `num_ineq` inequality and `num_eq` equality constraints.
+    pub fn new(n: usize, num_ineq: usize, num_eq: usize) -> Result<Self> {
+        ensure!(n > 0, "Dimension must be positive");
+        Ok(Self {
+            gamma_ineq: DVector::zeros(num_ineq),
+            omega_ineq: DVector::from_element(num_ineq, 1.0),
+            gamma_eq: DVector::zeros(num_eq),
+            omega_eq: DVector::from_element(num_eq, 1.0),
+            chi: 2.0,
+            k1: 3.0,
+            k2: 5.0,
+            d_gamma: 5.0,
+            n: n as f32,
+            best_feasible: None,
+            prev_mean: None,
+        })
+    }
+
+    /// Augmented fitness of a single individual.
+    pub fn augmented(&self, f: f32, g: &RowDVector<f32>, h: &RowDVector<f32>) -> f32 {
+        let ineq: f32 = (0..g.len())
+            .map(|i| {
+                let (gamma, omega) = (self.gamma_ineq[i], self.omega_ineq[i]);
+                if gamma + omega * g[i] >= 0.0 {
+                    gamma * g[i] + omega / 2.0 * g[i] * g[i]
+                } else {
+                    -gamma * gamma / (2.0 * omega)
+                }
+            })
+            .sum();
+        let eq: f32 = (0..h.len())
+            .map(|j| self.gamma_eq[j] * h[j] + self.omega_eq[j] / 2.0 * h[j] * h[j])
+            .sum();
+        f + ineq + eq
+    }
+
+    /// Replaces raw fitness values by their augmented counterparts.
+    pub fn penalize(&self, fitness: &mut Fitness, ineq: &DMatrix<f32>, eq: &DMatrix<f32>) {
+        for i in 0..fitness.values.nrows() {
+            fitness.values[i] = self.augmented(
+                fitness.values[i],
+                &ineq.row(i).into_owned(),
+                &eq.row(i).into_owned(),
+            );
+        }
+    }
+
+    /// Adapts multipliers and penalty coefficients from `f`, `g` and `h` at the new mean.
+    pub fn update(&mut self, f: f32, g: &RowDVector<f32>, h: &RowDVector<f32>) {
+        if let Some((f_prev, g_prev, h_prev)) = self.prev_mean.take() {
+            // Change of the augmented fitness with the current coefficients
+            let delta_aug =
+                (self.augmented(f, g, h) - self.augmented(f_prev, &g_prev, &h_prev)).abs() / self.n;
+            let adapt = |omega: f32, c: f32, c_prev: f32| {
+                if omega * c * c < self.k1 * delta_aug
+                    || self.k2 * (c - c_prev).abs() < c_prev.abs()
+                {
+                    omega * self.chi.powf(1.0 / (4.0 * self.n))
+                } else {
+                    omega * self.chi.powf(-1.0 / self.n)
+                }
+            };
+            let omega_ineq =
+                DVector::from_fn(g.len(), |i, _| adapt(self.omega_ineq[i], g[i], g_prev[i]));
+            let omega_eq =
+                DVector::from_fn(h.len(), |j, _| adapt(self.omega_eq[j], h[j], h_prev[j]));
+            self.update_multipliers(g, h);
+            self.omega_ineq = omega_ineq;
+            self.omega_eq = omega_eq;
+        } else {
+            self.update_multipliers(g, h);
+        }
+        self.prev_mean = Some((f, g.clone(), h.clone()));
+    }
+
+    /// Moves the multipliers with the current penalty coefficients.
+    fn update_multipliers(&mut self, g: &RowDVector<f32>, h: &RowDVector<f32>) {
+        for i in 0..g.len() {
+            self.gamma_ineq[i] =
+                (self.gamma_ineq[i] + self.omega_ineq[i] * g[i] / self.d_gamma).max(0.0);
+        }
+        for j in 0..h.len() {
+            self.gamma_eq[j] += self.omega_eq[j] * h[j] / self.d_gamma;
+        }
+    }
+
+    /// Runs one constrained generation: ask, evaluate, penalize, tell and adapt.
+    ///
+    /// The mean of the new distribution is evaluated once more to adapt the
+    /// coefficients, which is accounted for in `evals_count`. The returned
+    /// state's best is by augmented fitness; see `best_feasible` for the optimum.
+    pub fn step<F, C>(
+        &mut self,
+        cmaes: &CmaesAlgo,
+        mut state: CmaesState,
+        objective: &F,
+        constraints: &C,
+    ) -> Result<CmaesState>
+    where
+        F: FitnessFunction,
+        C: ConstraintFunction,
+    {
+        let mut pop = cmaes.ask(&mut state)?;
+        let mut fitness = objective.evaluate(&pop)?;
+        let ineq = constraints.inequality(&pop);
+        let eq = constraints.equality(&pop);
+        ensure!(
+            ineq.ncols() == self.gamma_ineq.len() && eq.ncols() == self.gamma_eq.len(),
+            "Number of constraints does not match the handler"
+        );
+
+        // Keep track of the best feasible candidate by raw fitness
+        let violation = constraints.violation(&pop);
+        for i in 0..pop.y.nrows() {
+            let better = match &self.best_feasible {
+                Some((_, best)) => fitness.values[i] < *best,
+                None => true,
+            };
+            if violation[i] <= 0.0 && better {
+                self.best_feasible = Some((pop.y.row(i).transpose(), fitness.values[i]));
+            }
+        }
+
+        self.penalize(&mut fitness, &ineq, &eq);
+        let mut state = cmaes.tell(state, &mut pop, &mut fitness)?;
+
+        // Evaluate at the new mean and adapt the coefficients
+        let at_mean = PopulationY {
+            y: DMatrix::from_row_slice(1, state.mean.len(), state.mean.as_slice()),
+        };
+        let f = objective.evaluate(&at_mean)?.values[0];
+        let g: RowDVector<f32> = constraints.inequality(&at_mean).row(0).into_owned();
+        let h: RowDVector<f32> = constraints.equality(&at_mean).row(0).into_owned();
+        self.update(f, &g, &h);
+        state.evals_count += 1;
+
+        Ok(state)
+    }
+}
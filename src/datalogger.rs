@@ -0,0 +1,157 @@
+use crate::fitness::{Fitness, PopulationY};
+use crate::state::CmaesState;
+use anyhow::{ensure, Result};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Data files written by the logger, with pycma's headers.
+const FILES: [(&str, &str); 5] = [
+    (
+        "fit",
+        "% # columns=\"iteration, evaluation, sigma, axis ratio, bestever, best, median, worst objective function value, further objective values of best\"",
+    ),
+    (
+        "xmean",
+        "% # columns=\"iteration, evaluation, void, void, void, xmean\"",
+    ),
+    (
+        "stddev",
+        "% # columns=\"iteration, evaluation, sigma, void, void, stds==sigma*sqrt(diag(C))\"",
+    ),
+    (
+        "axlen",
+        "% # columns=\"iteration, evaluation, sigma, max axis length, min axis length, all principal axes lengths (sorted square roots of eigenvalues of C)\"",
+    ),
+    ("xrecentbest", "% # iter+eval+sigma+0+fitness+xbest"),
+];
+
+fn join(values: impl Iterator<Item = f32>) -> String {
+    values.map(|v| v.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+/// Writes the `outcmaes` files of pycma's `CMADataLogger`.
+///
+/// Every `every` generations, and on the first one, `add` appends a line to
+/// `fit.dat`, `xmean.dat`, `stddev.dat`, `axlen.dat` and `xrecentbest.dat`
+/// in `dir`, so pycma's plotting (`cma.plot(name_prefix)`) reads the run.
+/// Objective values are logged as minimized, i.e. negated for maximization.
+///
+/// ```rust
+/// use haru_cmaes::datalogger::CmaDataLogger;
+/// use haru_cmaes::fitness::{FitnessEvaluator, MinOrMax};
+/// use haru_cmaes::objectives::SquareAndSum;
+/// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+/// use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+/// use haru_cmaes::strategy::{CmaesAlgo, CmaesAlgoOptimizer};
+///
+/// let dir = std::env::temp_dir().join("haru_cmaes_outcmaes_doc");
+/// let mut logger = CmaDataLogger::new(&dir, 5).unwrap();
+///
+/// let params = CmaesParams::new().unwrap();
+/// let cmaes = CmaesAlgo::new(params).unwrap();
+/// let mut state = CmaesState::init_state(&cmaes.params).unwrap();
+/// let obj_func = SquareAndSum { obj_dim: 6, dir: MinOrMax::Min };
+/// for _ in 0..20 {
+///     let mut pop = cmaes.ask(&mut state).unwrap();
+///     let mut fitness = obj_func.evaluate(&pop).unwrap();
+///     state = cmaes.tell(state, &mut pop, &mut fitness).unwrap();
+///     logger.add(&state, &pop, &fitness).unwrap();
+/// }
+///
+/// // Header, then generations 1, 5, 10, 15 and 20
+/// let fit = std::fs::read_to_string(dir.join("fit.dat")).unwrap();
+/// assert_eq!(fit.lines().count(), 6);
+/// let last: Vec<&str> = fit.lines().last().unwrap().split(' ').collect();
+/// assert_eq!(&last[..2], &["20", "200"]);
+/// let xmean = std::fs::read_to_string(dir.join("xmean.dat")).unwrap();
+/// assert_eq!(xmean.lines().last().unwrap().split(' ').count(), 5 + 6);
+/// ```
+pub struct CmaDataLogger {
+    pub dir: PathBuf,
+    pub every: i32, // Generations between lines
+    files: Vec<BufWriter<File>>,
+}
+
+impl CmaDataLogger {
+    /// Creates `dir` and starts the data files, replacing previous ones.
+    pub fn new(dir: impl AsRef<Path>, every: i32) -> Result<Self> {
+        ensure!(every >= 1, "Logging interval must be at least 1");
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let seed = fastrand::get_seed();
+        let files = FILES
+            .iter()
+            .map(|(name, header)| {
+                let mut file = BufWriter::new(File::create(dir.join(format!("{}.dat", name)))?);
+                writeln!(file, "{}, seed={}", header, seed)?;
+                file.flush()?;
+                Ok(file)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { dir, every, files })
+    }
+
+    /// Logs the generation just told, from its sorted population and fitness.
+    pub fn add(&mut self, state: &CmaesState, pop: &PopulationY, fitness: &Fitness) -> Result<()> {
+        if state.g != 1 && state.g % self.every != 0 {
+            return Ok(());
+        }
+        ensure!(!fitness.values.is_empty(), "Cannot log an empty generation");
+        let (g, evals, sigma) = (state.g, state.evals_count, state.sigma);
+        let values = &fitness.values;
+        let lam = values.len();
+        let median = if lam % 2 == 1 {
+            values[lam / 2]
+        } else {
+            (values[lam / 2 - 1] + values[lam / 2]) / 2.
+        };
+        let mut axes: Vec<f32> = state.eig_vals.iter().map(|e| e.max(0.).sqrt()).collect();
+        axes.sort_by(|a, b| a.total_cmp(b));
+        let (min_axis, max_axis) = (axes[0], axes[axes.len() - 1]);
+
+        let lines = [
+            format!(
+                "{} {} {} {} {} {} {} {}",
+                g,
+                evals,
+                sigma,
+                max_axis / min_axis,
+                state.best_y_fit[0],
+                values[0],
+                median,
+                values[lam - 1]
+            ),
+            format!("{} {} 0 0 0 {}", g, evals, join(state.mean.iter().copied())),
+            format!(
+                "{} {} {} 0 0 {}",
+                g,
+                evals,
+                sigma,
+                join(state.cov.diagonal().iter().map(|c| sigma * c.sqrt()))
+            ),
+            format!(
+                "{} {} {} {} {} {}",
+                g,
+                evals,
+                sigma,
+                max_axis,
+                min_axis,
+                join(axes.iter().copied())
+            ),
+            format!(
+                "{} {} {} 0 {} {}",
+                g,
+                evals,
+                sigma,
+                values[0],
+                join(pop.y.row(0).iter().copied())
+            ),
+        ];
+        for (file, line) in self.files.iter_mut().zip(lines) {
+            writeln!(file, "{}", line)?;
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,285 @@
+use crate::fitness::{FitnessEvaluator, MinOrMax};
+use crate::objectives::DEAProblem;
+use crate::params::CmaesParams;
+use crate::state::{CmaesState, CmaesStateLogic};
+use crate::strategy::{Budget, CmaesAlgo, CmaesAlgoOptimizer};
+use anyhow::{anyhow, ensure, Context, Result};
+use nalgebra::{DMatrix, DVector};
+use std::fs;
+use std::path::Path;
+
+/// Decision-making units, with output columns first and input columns last.
+///
+/// ```rust
+/// use haru_cmaes::dea::DeaData;
+///
+/// let csv = "name,staff,cost,sales\nA,2,5,10\nB,4,3,12\n";
+/// let data = DeaData::from_csv_str(csv, Some("name"), &["sales"], &["staff", "cost"]).unwrap();
+///
+/// assert_eq!(data.names, vec!["A", "B"]);
+/// assert_eq!(data.data.row(1).iter().copied().collect::<Vec<_>>(), vec![12.0, 4.0, 3.0]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DeaData {
+    pub names: Vec<String>, // Unit identifiers
+    pub output_dim: usize,
+    pub input_dim: usize,
+    pub data: DMatrix<f32>, // One row per unit
+}
+
+impl DeaData {
+    /// Loads units from a CSV file with a header row.
+    ///
+    /// `id_column` names the units, otherwise they are numbered from 0.
+    pub fn from_csv(
+        path: impl AsRef<Path>,
+        id_column: Option<&str>,
+        outputs: &[&str],
+        inputs: &[&str],
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let text =
+            fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+        Self::from_csv_str(&text, id_column, outputs, inputs)
+    }
+
+    /// Parses units from CSV text with a header row.
+    pub fn from_csv_str(
+        text: &str,
+        id_column: Option<&str>,
+        outputs: &[&str],
+        inputs: &[&str],
+    ) -> Result<Self> {
+        ensure!(
+            !outputs.is_empty() && !inputs.is_empty(),
+            "At least one output and one input column are required"
+        );
+        let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+        let header: Vec<&str> = lines
+            .next()
+            .ok_or_else(|| anyhow!("CSV has no header row"))?
+            .split(',')
+            .map(|h| h.trim().trim_matches('"'))
+            .collect();
+        let find = |name: &str| {
+            header
+                .iter()
+                .position(|h| *h == name)
+                .ok_or_else(|| anyhow!("Column '{}' not found in CSV header", name))
+        };
+        let columns = outputs
+            .iter()
+            .chain(inputs.iter())
+            .map(|c| find(c))
+            .collect::<Result<Vec<usize>>>()?;
+        let id = id_column.map(find).transpose()?;
+
+        let mut names = Vec::new();
+        let mut values = Vec::new();
+        for (row, line) in lines.enumerate() {
+            let fields: Vec<&str> = line
+                .split(',')
+                .map(|f| f.trim().trim_matches('"'))
+                .collect();
+            ensure!(
+                fields.len() == header.len(),
+                "Row {} has {} fields, expected {}",
+                row + 1,
+                fields.len(),
+                header.len()
+            );
+            names.push(id.map_or_else(|| row.to_string(), |i| fields[i].to_string()));
+            for &c in &columns {
+                let value: f32 = fields[c]
+                    .parse()
+                    .with_context(|| format!("Row {}, column '{}'", row + 1, header[c]))?;
+                ensure!(value > 0.0, "DEA requires positive data, row {}", row + 1);
+                values.push(value);
+            }
+        }
+        ensure!(!names.is_empty(), "CSV has no data rows");
+
+        Ok(Self {
+            data: DMatrix::from_row_slice(names.len(), columns.len(), &values),
+            names,
+            output_dim: outputs.len(),
+            input_dim: inputs.len(),
+        })
+    }
+
+    /// Number of decision-making units.
+    pub fn num_units(&self) -> usize {
+        self.data.nrows()
+    }
+
+    /// The DEA problem of `unit`, i.e. with its row moved first.
+    pub fn problem(&self, unit: usize) -> DEAProblem {
+        let mut data = self.data.clone();
+        data.swap_rows(0, unit);
+        DEAProblem {
+            obj_dim: self.output_dim + self.input_dim,
+            dir: MinOrMax::Max,
+            output_dim: self.output_dim,
+            input_dim: self.input_dim,
+            data,
+        }
+    }
+
+    /// Output over input ratio of every unit under the given weights.
+    pub fn ratios(&self, weights: &DVector<f32>) -> DVector<f32> {
+        let out = self.data.columns(0, self.output_dim) * weights.rows(0, self.output_dim);
+        let inp = self.data.columns(self.output_dim, self.input_dim)
+            * weights.rows(self.output_dim, self.input_dim);
+        out.component_div(&inp)
+    }
+}
+
+/// Efficiencies, weights and cross-efficiencies of all units.
+#[derive(Debug, Clone)]
+pub struct DeaReport {
+    pub names: Vec<String>,
+    pub efficiency: DVector<f32>, // Efficiency of each unit under its own weights
+    pub weights: DMatrix<f32>,    // One row per unit, output weights then input weights
+    pub cross_efficiency: DMatrix<f32>, // Row k: efficiencies of all units under the weights of unit k
+}
+
+impl DeaReport {
+    /// Mean efficiency of each unit under the weights of all the others.
+    pub fn mean_cross_efficiency(&self) -> DVector<f32> {
+        let n = self.names.len();
+        DVector::from_fn(n, |j, _| {
+            if n > 1 {
+                (self.cross_efficiency.column(j).sum() - self.cross_efficiency[(j, j)])
+                    / (n - 1) as f32
+            } else {
+                self.cross_efficiency[(j, j)]
+            }
+        })
+    }
+
+    /// Efficiencies and weights as CSV text, one row per unit.
+    pub fn to_csv(&self, data: &DeaData) -> String {
+        let mut out = String::from("unit,efficiency,mean_cross_efficiency");
+        for i in 0..data.output_dim {
+            out.push_str(&format!(",u{}", i));
+        }
+        for i in 0..data.input_dim {
+            out.push_str(&format!(",v{}", i));
+        }
+        out.push('\n');
+        let cross = self.mean_cross_efficiency();
+        for (k, name) in self.names.iter().enumerate() {
+            out.push_str(&format!("{},{},{}", name, self.efficiency[k], cross[k]));
+            for w in self.weights.row(k).iter() {
+                out.push_str(&format!(",{}", w));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Runs one CMA-ES per decision-making unit.
+///
+/// Each unit maximizes its `DEAProblem`. The best weights are then scaled so
+/// that the unit's weighted inputs sum to one and the most efficient unit
+/// under them scores exactly one; cross-efficiencies use these weights.
+///
+/// ```rust
+/// use haru_cmaes::dea::{DeaData, DeaDriver};
+/// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+///
+/// // One output, one input: efficiency is the ratio relative to the best unit
+/// let csv = "unit,output,input\nA,2,1\nB,3,2\nC,1,1\n";
+/// let data = DeaData::from_csv_str(csv, Some("unit"), &["output"], &["input"]).unwrap();
+/// let params = CmaesParams::new()
+///     .and_then(|p| p.set_xstart(2, 1.0))
+///     .and_then(|p| p.set_num_gens(50))
+///     .unwrap();
+/// let report = DeaDriver::new(params).run(&data).unwrap();
+///
+/// assert!((report.efficiency[0] - 1.0).abs() < 1e-5);
+/// assert!((report.efficiency[1] - 0.75).abs() < 1e-5);
+/// assert!((report.efficiency[2] - 0.5).abs() < 1e-5);
+/// assert!(report.cross_efficiency.iter().all(|&e| e <= 1.0 + 1e-5));
+/// ```
+#[derive(Debug, Clone)]
+pub struct DeaDriver {
+    pub params: CmaesParams,
+}
+
+impl DeaDriver {
+    pub fn new(params: CmaesParams) -> Self {
+        Self { params }
+    }
+
+    /// Optimizes the weights of a single unit, normalized and feasible.
+    ///
+    /// Each unit gets the full evaluation and time budget of the parameters.
+    pub fn weights(&self, data: &DeaData, unit: usize) -> Result<DVector<f32>> {
+        let problem = data.problem(unit);
+        let cmaes = CmaesAlgo::new(self.params.clone())?;
+        let mut state = CmaesState::init_state(&cmaes.params)?;
+        let mut budget = Budget::from_params(&cmaes.params, &state);
+        for step in 1..=self.params.num_gens {
+            if budget.exhausted().is_some() {
+                break;
+            }
+            let mut pop = cmaes.ask(&mut state)?;
+            let lam = budget.candidates(pop.y.nrows());
+            if lam < pop.y.nrows() {
+                pop.y = pop.y.rows(0, lam).into_owned();
+            }
+            let mut fitness = problem.evaluate(&pop)?;
+            budget.spend(lam);
+            // Degenerate weights, such as a zero denominator, rank worst
+            fitness
+                .values
+                .apply(|f| *f = if f.is_finite() { *f } else { f32::MAX });
+            state = cmaes.tell_truncated(state, &mut pop, &mut fitness)?;
+            if cmaes.is_done(&state, step)? {
+                break;
+            }
+        }
+
+        // Weighted inputs of the unit sum to one, all ratios at most one
+        let (best_y, _) = state.get_best()?;
+        let mut weights = best_y.abs();
+        let input = data.data.row(unit).columns(data.output_dim, data.input_dim)
+            * weights.rows(data.output_dim, data.input_dim);
+        ensure!(input[0] > 0.0, "Unit {} has all input weights zero", unit);
+        weights /= input[0];
+        let max_ratio = data.ratios(&weights).max();
+        ensure!(max_ratio > 0.0, "Unit {} has all output weights zero", unit);
+        weights
+            .rows_mut(0, data.output_dim)
+            .scale_mut(1.0 / max_ratio);
+        Ok(weights)
+    }
+
+    /// Optimizes every unit and gathers the results.
+    pub fn run(&self, data: &DeaData) -> Result<DeaReport> {
+        let dim = data.output_dim + data.input_dim;
+        ensure!(
+            self.params.xstart.len() == dim,
+            "Parameters have dimension {}, data has {} weights",
+            self.params.xstart.len(),
+            dim
+        );
+        let n = data.num_units();
+        let mut weights = DMatrix::zeros(n, dim);
+        let mut cross_efficiency = DMatrix::zeros(n, n);
+        for k in 0..n {
+            let w = self.weights(data, k)?;
+            cross_efficiency.set_row(k, &data.ratios(&w).transpose());
+            weights.set_row(k, &w.transpose());
+        }
+
+        Ok(DeaReport {
+            names: data.names.clone(),
+            efficiency: cross_efficiency.diagonal(),
+            weights,
+            cross_efficiency,
+        })
+    }
+}
@@ -0,0 +1,429 @@
+use crate::fitness::{FitnessFunction, PopulationY};
+use crate::metrics::{timed, Phase};
+use crate::objectives;
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use nalgebra::{DMatrix, DVector};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Pause between polls for connections, and asked of idle workers.
+const POLL: Duration = Duration::from_millis(10);
+
+/// Messages from a worker, one JSON object per line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkerMessage {
+    Ready, // Asks for a task
+    Result {
+        id: u64,
+        #[serde(with = "wire_value")]
+        value: f32, // Non-finite as "inf", "-inf" or "nan", which JSON numbers cannot carry
+    }, // Answers task `id` and asks for the next
+}
+
+/// Objective values as JSON numbers, or strings for the non-finite ones.
+mod wire_value {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Wire {
+        Number(f32),
+        Text(String),
+    }
+
+    pub fn serialize<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+        let wire = match *value {
+            v if v.is_finite() => Wire::Number(v),
+            v if v.is_nan() => Wire::Text("nan".into()),
+            v if v > 0.0 => Wire::Text("inf".into()),
+            _ => Wire::Text("-inf".into()),
+        };
+        wire.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+        match Wire::deserialize(deserializer)? {
+            Wire::Number(v) => Ok(v),
+            Wire::Text(text) => match text.as_str() {
+                "inf" => Ok(f32::INFINITY),
+                "-inf" => Ok(f32::NEG_INFINITY),
+                "nan" => Ok(f32::NAN),
+                _ => Err(D::Error::custom(format!("invalid value '{}'", text))),
+            },
+        }
+    }
+}
+
+/// Messages from the coordinator, one JSON object per line, one per worker message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CoordinatorMessage {
+    Task {
+        id: u64,
+        objective: String, // Registered name of the objective
+        x: Vec<f32>,
+    }, // One candidate to evaluate
+    Wait {
+        ms: u64,
+    }, // Nothing to hand out, ask again after this many milliseconds
+    Done, // The coordinator is shutting down
+}
+
+/// Writes `message` as one line, in a single write so it leaves at once.
+fn send(stream: &mut TcpStream, message: &impl Serialize) -> Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    stream.write_all(&line)?;
+    Ok(())
+}
+
+/// Candidate of the population being evaluated.
+struct Slot {
+    x: Vec<f32>,
+    value: Option<f32>,
+    issued: Option<Instant>, // Last time it was handed out
+}
+
+/// Population being evaluated, shared with the connection threads.
+struct Queue {
+    slots: Vec<Slot>,
+    tickets: HashMap<u64, usize>, // Open tasks of the population, to their slot
+    missing: usize,
+    next_id: u64,
+    workers: usize,
+    closed: bool,
+}
+
+struct Shared {
+    objective: String,
+    timeout: Duration,
+    queue: Mutex<Queue>,
+    answered: Condvar, // Signalled when the population is complete
+}
+
+impl Shared {
+    fn lock(&self) -> Result<MutexGuard<'_, Queue>> {
+        self.queue
+            .lock()
+            .map_err(|_| anyhow!("A connection thread panicked"))
+    }
+
+    /// Records a worker message and picks the answer.
+    fn reply(&self, message: WorkerMessage) -> Result<CoordinatorMessage> {
+        let mut queue = self.lock()?;
+        if let WorkerMessage::Result { id, value } = message {
+            // Late answers of re-dispatched tasks and of past populations are dropped
+            if let Some(slot) = queue.tickets.remove(&id) {
+                if queue.slots[slot].value.is_none() {
+                    queue.slots[slot].value = Some(value);
+                    queue.missing -= 1;
+                    if queue.missing == 0 {
+                        self.answered.notify_all();
+                    }
+                }
+            }
+        }
+        if queue.closed {
+            return Ok(CoordinatorMessage::Done);
+        }
+
+        let now = Instant::now();
+        let next = queue
+            .slots
+            .iter()
+            .position(|s| s.issued.is_none())
+            .or_else(|| {
+                queue.slots.iter().position(|s| {
+                    s.value.is_none() && s.issued.is_some_and(|t| now - t >= self.timeout)
+                })
+            });
+        let Some(slot) = next else {
+            return Ok(CoordinatorMessage::Wait {
+                ms: POLL.as_millis() as u64,
+            });
+        };
+        let id = queue.next_id;
+        queue.next_id += 1;
+        queue.tickets.insert(id, slot);
+        queue.slots[slot].issued = Some(now);
+        Ok(CoordinatorMessage::Task {
+            id,
+            objective: self.objective.clone(),
+            x: queue.slots[slot].x.clone(),
+        })
+    }
+
+    /// Serves one worker until it leaves, goes silent or is told `Done`.
+    fn serve(&self, stream: TcpStream) -> Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_nodelay(true)?;
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let message: WorkerMessage =
+                serde_json::from_str(&line?).context("Invalid worker message")?;
+            let reply = self.reply(message)?;
+            send(&mut writer, &reply)?;
+            if reply == CoordinatorMessage::Done {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Evaluates populations on workers connected over TCP.
+///
+/// Workers connect to the bound address and exchange line-delimited JSON:
+/// they send `WorkerMessage`s and get one `CoordinatorMessage` back for each.
+/// `evaluate` hands the rows of a population out as tasks, one per request,
+/// and returns once every candidate has a value. A task unanswered after
+/// `timeout` is re-dispatched to the next worker asking, and a worker silent
+/// for that long is disconnected; whichever answer comes first is kept.
+/// Workers may join and leave at any time.
+///
+/// ```rust
+/// use haru_cmaes::distributed::{Coordinator, Worker};
+/// use haru_cmaes::fitness::Fitness;
+/// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+/// use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+/// use haru_cmaes::strategy::{CmaesAlgo, CmaesAlgoOptimizer};
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// let coordinator = Coordinator::bind("127.0.0.1:0", "sphere", Duration::from_secs(5)).unwrap();
+/// let addr = coordinator.local_addr();
+/// let workers: Vec<_> = (0..3)
+///     .map(|_| thread::spawn(move || Worker::with_builtins().run(addr).unwrap()))
+///     .collect();
+///
+/// let params = CmaesParams::new().and_then(|p| p.set_xstart(4, 1.0)).unwrap();
+/// let cmaes = CmaesAlgo::new(params).unwrap();
+/// let mut state = CmaesState::init_state(&cmaes.params).unwrap();
+/// let popsize = cmaes.params.popsize as usize;
+/// for _ in 0..50 {
+///     let mut pop = cmaes.ask(&mut state).unwrap();
+///     let values = coordinator.evaluate(&pop).unwrap();
+///     state = cmaes.tell(state, &mut pop, &mut Fitness { values }).unwrap();
+/// }
+///
+/// // Workers are told to stop when the coordinator goes away
+/// drop(coordinator);
+/// let evaluations: usize = workers.into_iter().map(|w| w.join().unwrap()).sum();
+/// assert_eq!(evaluations, 50 * popsize);
+/// assert!(state.best_y_fit[0] < 1e-3);
+/// ```
+pub struct Coordinator {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+}
+
+impl Coordinator {
+    /// Listens on `addr` for workers evaluating the objective registered as `objective`.
+    pub fn bind(addr: impl ToSocketAddrs, objective: &str, timeout: Duration) -> Result<Self> {
+        ensure!(!timeout.is_zero(), "Worker timeout must be positive");
+        let listener = TcpListener::bind(addr).context("Cannot bind the coordinator")?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let shared = Arc::new(Shared {
+            objective: objective.to_string(),
+            timeout,
+            queue: Mutex::new(Queue {
+                slots: Vec::new(),
+                tickets: HashMap::new(),
+                missing: 0,
+                next_id: 0,
+                workers: 0,
+                closed: false,
+            }),
+            answered: Condvar::new(),
+        });
+
+        let accepting = shared.clone();
+        thread::spawn(move || loop {
+            match accepting.lock() {
+                Ok(queue) if !queue.closed => {}
+                _ => return,
+            }
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let shared = accepting.clone();
+                    thread::spawn(move || {
+                        shared.lock().map(|mut q| q.workers += 1).ok();
+                        let _served = shared.serve(stream);
+                        #[cfg(feature = "tracing")]
+                        if let Err(error) = &_served {
+                            tracing::debug!(%error, "worker disconnected");
+                        }
+                        shared.lock().map(|mut q| q.workers -= 1).ok();
+                    });
+                }
+                // Nothing pending, or a connection that failed before accept
+                Err(_) => thread::sleep(POLL),
+            }
+        });
+        Ok(Self { addr, shared })
+    }
+
+    /// Address workers connect to, with the actual port when bound to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Workers currently connected.
+    pub fn workers(&self) -> usize {
+        self.shared.lock().map(|q| q.workers).unwrap_or(0)
+    }
+
+    /// Objective values of the population, as the workers' `cost` returns them.
+    ///
+    /// Non-finite values come back as sent. Blocks until every candidate is
+    /// answered, waiting for workers to connect if none are, or fails once
+    /// the coordinator is closed.
+    pub fn evaluate(&self, pop: &PopulationY) -> Result<DVector<f32>> {
+        let mut queue = self.shared.lock()?;
+        queue.slots = pop
+            .y
+            .row_iter()
+            .map(|row| Slot {
+                x: row.iter().copied().collect(),
+                value: None,
+                issued: None,
+            })
+            .collect();
+        queue.tickets.clear();
+        queue.missing = queue.slots.len();
+        while queue.missing > 0 {
+            ensure!(!queue.closed, "The coordinator was closed");
+            queue = self
+                .shared
+                .answered
+                .wait(queue)
+                .map_err(|_| anyhow!("A connection thread panicked"))?;
+        }
+        let values = queue.slots.iter().filter_map(|s| s.value);
+        let values = DVector::from_iterator(pop.y.nrows(), values);
+        queue.slots.clear();
+        queue.tickets.clear();
+        Ok(values)
+    }
+
+    /// Stops accepting workers, tells connected ones `Done` and fails `evaluate`.
+    pub fn close(&self) {
+        if let Ok(mut queue) = self.shared.lock() {
+            queue.closed = true;
+            self.shared.answered.notify_all();
+        }
+    }
+}
+
+impl Drop for Coordinator {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+type Factory = Box<dyn Fn(usize) -> Result<Box<dyn FitnessFunction>>>;
+
+/// Evaluates tasks of a `Coordinator` with objectives registered by name.
+///
+/// Objectives are built once per dimension, from the length of the first
+/// candidate of that size. See `Coordinator` for an example.
+#[derive(Default)]
+pub struct Worker {
+    pub retry: Duration, // How long to keep trying to connect
+    objectives: HashMap<String, Factory>,
+}
+
+impl Worker {
+    /// Worker without objectives.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Worker with the built-in objectives of `objectives::by_name`.
+    pub fn with_builtins() -> Self {
+        let names = objectives::NAMES.map(str::to_string);
+        let bbob = (1..=24).map(|f| format!("bbob-{}", f));
+        names
+            .into_iter()
+            .chain(bbob)
+            .fold(Self::new(), |worker, name| {
+                let key = name.clone();
+                worker.register(&key, move |dim| objectives::by_name(&name, dim))
+            })
+    }
+
+    /// Adds or replaces the objective `name`, built from the dimension.
+    pub fn register(
+        mut self,
+        name: &str,
+        factory: impl Fn(usize) -> Result<Box<dyn FitnessFunction>> + 'static,
+    ) -> Self {
+        self.objectives.insert(name.to_string(), Box::new(factory));
+        self
+    }
+
+    fn connect(&self, addr: impl ToSocketAddrs) -> Result<TcpStream> {
+        let start = Instant::now();
+        loop {
+            match TcpStream::connect(&addr) {
+                Ok(stream) => {
+                    stream.set_nodelay(true)?;
+                    return Ok(stream);
+                }
+                Err(_) if start.elapsed() < self.retry => thread::sleep(POLL * 10),
+                Err(e) => return Err(e).context("Cannot connect to the coordinator"),
+            }
+        }
+    }
+
+    /// Evaluates tasks until the coordinator is done or closes the connection.
+    ///
+    /// Returns the number of evaluations. Fails on a task for an objective
+    /// that is not registered.
+    pub fn run(&self, addr: impl ToSocketAddrs) -> Result<usize> {
+        let stream = self.connect(addr)?;
+        let mut writer = stream.try_clone()?;
+        let mut lines = BufReader::new(stream).lines();
+        let mut built: HashMap<(String, usize), Box<dyn FitnessFunction>> = HashMap::new();
+        let mut evaluations = 0;
+
+        let mut message = WorkerMessage::Ready;
+        loop {
+            send(&mut writer, &message)?;
+            let Some(line) = lines.next() else {
+                return Ok(evaluations);
+            };
+            message = match serde_json::from_str(&line?).context("Invalid coordinator message")? {
+                CoordinatorMessage::Task { id, objective, x } => {
+                    let key = (objective, x.len());
+                    if !built.contains_key(&key) {
+                        let Some(factory) = self.objectives.get(&key.0) else {
+                            bail!("Objective '{}' is not registered", key.0);
+                        };
+                        built.insert(key.clone(), factory(x.len())?);
+                    }
+                    let pop = PopulationY {
+                        y: DMatrix::from_row_slice(1, x.len(), &x),
+                    };
+                    let value = timed(Phase::Evaluation, || built[&key].cost(&pop)[0]);
+                    evaluations += 1;
+                    WorkerMessage::Result { id, value }
+                }
+                CoordinatorMessage::Wait { ms } => {
+                    thread::sleep(Duration::from_millis(ms));
+                    WorkerMessage::Ready
+                }
+                CoordinatorMessage::Done => return Ok(evaluations),
+            };
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::metrics::{timed, Phase};
 use anyhow::Result;
 use nalgebra::{DMatrix, DVector};
 
@@ -40,8 +41,14 @@ where
     type IndividualsEvaluated = Fitness;
     type ObjectiveDim = usize;
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "evaluate", level = "debug", skip_all, fields(popsize = pop.y.nrows()))
+    )]
     fn evaluate(&self, pop: &PopulationY) -> Result<Self::IndividualsEvaluated> {
-        let mut values = self.cost(pop);
+        let mut values = timed(Phase::Evaluation, || self.cost(pop));
+        #[cfg(feature = "tracing")]
+        tracing::trace!(best = values.min(), worst = values.max(), "evaluated");
         let multiplier = match self.optimization_type() {
             MinOrMax::Min => 1.0,
             MinOrMax::Max => -1.0,
@@ -4,10 +4,27 @@
 //!
 //! For detailed usage and examples, please refer to `lib.rs` and the examples directory.
 
+pub mod bbob;
+pub mod benchmark;
+pub mod coco;
+pub mod constraints;
+pub mod datalogger;
+pub mod dea;
+#[cfg(feature = "distributed")]
+pub mod distributed;
 pub mod fitness;
+pub mod metrics;
+pub mod nes;
 pub mod objectives;
+pub mod observer;
+pub mod optimize;
 pub mod params;
+#[cfg(feature = "plot")]
+pub mod plot;
 pub mod state;
 pub mod strategy;
+pub mod surrogate;
+pub mod tickets;
+pub mod transforms;
 
 pub mod utils;
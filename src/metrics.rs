@@ -0,0 +1,319 @@
+use crate::state::CmaesState;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::fmt;
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use std::time::Instant;
+
+/// Phases of a generation that are timed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Sampling,           // Drawing and scaling the population in `ask`
+    Eigendecomposition, // Decomposing the covariance in `ask`
+    Evaluation,         // Objective function calls
+    Update,             // `tell`
+}
+
+const NUM_PHASES: usize = 4;
+
+thread_local! {
+    static ELAPSED: Cell<[Duration; NUM_PHASES]> = const { Cell::new([Duration::ZERO; NUM_PHASES]) };
+}
+
+/// Runs `f`, adding its duration to `phase` for the current thread.
+///
+/// `ask`, `tell` and `FitnessEvaluator::evaluate` time themselves; use this
+/// around evaluations done otherwise so they show up in the metrics.
+pub fn timed<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    let _timer = PhaseTimer::start(phase);
+    f()
+}
+
+/// Adds the time until it is dropped to a phase.
+pub(crate) struct PhaseTimer {
+    phase: Phase,
+    start: Instant,
+}
+
+impl PhaseTimer {
+    pub(crate) fn start(phase: Phase) -> Self {
+        Self {
+            phase,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for PhaseTimer {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        ELAPSED.with(|cell| {
+            let mut phases = cell.get();
+            phases[self.phase as usize] += elapsed;
+            cell.set(phases);
+        });
+    }
+}
+
+/// Phase durations since the last call, for the current thread.
+fn take_elapsed() -> [Duration; NUM_PHASES] {
+    ELAPSED.with(|cell| cell.replace([Duration::ZERO; NUM_PHASES]))
+}
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static CURRENT_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Global allocator counting allocations and live bytes, over `System`.
+///
+/// Install it in the binary to get allocation counts and peak heap usage in
+/// the metrics; without it these are `None`. Counts cover all threads.
+///
+/// ```rust
+/// use haru_cmaes::metrics::CountingAllocator;
+///
+/// #[global_allocator]
+/// static ALLOCATOR: CountingAllocator = CountingAllocator;
+///
+/// fn main() {
+///     let before = CountingAllocator::allocations().unwrap();
+///     let v = vec![0u8; 1024];
+///     assert!(CountingAllocator::allocations().unwrap() > before);
+///     assert!(CountingAllocator::current_bytes().unwrap() >= v.len());
+/// }
+/// ```
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    /// Allocations since start, if installed.
+    pub fn allocations() -> Option<u64> {
+        INSTALLED
+            .load(Ordering::Relaxed)
+            .then(|| ALLOCATIONS.load(Ordering::Relaxed))
+    }
+
+    /// Live heap bytes, if installed.
+    pub fn current_bytes() -> Option<usize> {
+        INSTALLED
+            .load(Ordering::Relaxed)
+            .then(|| CURRENT_BYTES.load(Ordering::Relaxed))
+    }
+
+    /// Largest live heap bytes since the last reset, if installed.
+    pub fn peak_bytes() -> Option<usize> {
+        INSTALLED
+            .load(Ordering::Relaxed)
+            .then(|| PEAK_BYTES.load(Ordering::Relaxed))
+    }
+
+    /// Restarts the peak from the live bytes.
+    pub fn reset_peak() {
+        PEAK_BYTES.store(CURRENT_BYTES.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    fn added(size: usize) {
+        INSTALLED.store(true, Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        let current = CURRENT_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK_BYTES.fetch_max(current, Ordering::Relaxed);
+    }
+
+    fn removed(size: usize) {
+        CURRENT_BYTES.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Self::added(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            Self::added(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        Self::removed(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            Self::removed(layout.size());
+            Self::added(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Resident set size in bytes, where `/proc` is available.
+pub fn resident_bytes() -> Option<usize> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kb: usize = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// Measurements of one generation.
+#[derive(Debug, Clone)]
+pub struct GenerationMetrics {
+    pub g: i32,
+    pub sampling: Duration,
+    pub eigendecomposition: Duration,
+    pub evaluation: Duration,
+    pub update: Duration,
+    pub allocations: Option<u64>,      // Needs `CountingAllocator`
+    pub peak_bytes: Option<usize>,     // Peak live heap, needs `CountingAllocator`
+    pub resident_bytes: Option<usize>, // Resident set size after `tell`
+}
+
+/// Collects per-generation timings and memory use.
+///
+/// Call `record` after each `tell`, or pass it as an observer to
+/// `rollout_observed`. Timings are those of the current thread; allocation
+/// counts and heap peaks need `CountingAllocator` as global allocator.
+///
+/// ```rust
+/// use haru_cmaes::fitness::MinOrMax;
+/// use haru_cmaes::metrics::Metrics;
+/// use haru_cmaes::objectives::SquareAndSum;
+/// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+/// use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+/// use haru_cmaes::strategy::{CmaesAlgo, CmaesAlgoOptimizer};
+///
+/// let params = CmaesParams::new().unwrap();
+/// let cmaes = CmaesAlgo::new(params).unwrap();
+/// let state = CmaesState::init_state(&cmaes.params).unwrap();
+/// let obj_func = SquareAndSum { obj_dim: 6, dir: MinOrMax::Min };
+/// let mut metrics = Metrics::new();
+/// cmaes.rollout_observed(state, obj_func, 30, &mut metrics).unwrap();
+///
+/// assert_eq!(metrics.generations.len(), 30);
+/// assert_eq!(metrics.generations[29].g, 30);
+/// let summary = metrics.summary();
+/// assert!(summary.update > std::time::Duration::ZERO);
+/// assert!(summary.allocations.is_none());
+/// println!("{}", summary);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    pub generations: Vec<GenerationMetrics>,
+    last_allocations: Option<u64>,
+}
+
+impl Metrics {
+    /// Starts collecting, discarding timings of earlier work.
+    pub fn new() -> Self {
+        take_elapsed();
+        CountingAllocator::reset_peak();
+        Self {
+            generations: Vec::new(),
+            last_allocations: CountingAllocator::allocations(),
+        }
+    }
+
+    /// Records the generation just told.
+    pub fn record(&mut self, state: &CmaesState) {
+        let elapsed = take_elapsed();
+        let allocations = CountingAllocator::allocations();
+        let delta = allocations.map(|a| a - self.last_allocations.unwrap_or(0));
+        self.last_allocations = allocations;
+        let peak_bytes = CountingAllocator::peak_bytes();
+        CountingAllocator::reset_peak();
+        self.generations.push(GenerationMetrics {
+            g: state.g,
+            sampling: elapsed[Phase::Sampling as usize],
+            eigendecomposition: elapsed[Phase::Eigendecomposition as usize],
+            evaluation: elapsed[Phase::Evaluation as usize],
+            update: elapsed[Phase::Update as usize],
+            allocations: delta,
+            peak_bytes,
+            resident_bytes: resident_bytes(),
+        });
+    }
+
+    /// Totals over the recorded generations.
+    pub fn summary(&self) -> MetricsSummary {
+        let gens = &self.generations;
+        let total = |f: fn(&GenerationMetrics) -> Duration| gens.iter().map(f).sum();
+        MetricsSummary {
+            generations: gens.len(),
+            sampling: total(|m| m.sampling),
+            eigendecomposition: total(|m| m.eigendecomposition),
+            evaluation: total(|m| m.evaluation),
+            update: total(|m| m.update),
+            allocations: gens.iter().map(|m| m.allocations).sum(),
+            peak_bytes: gens.iter().filter_map(|m| m.peak_bytes).max(),
+            peak_resident_bytes: gens.iter().filter_map(|m| m.resident_bytes).max(),
+        }
+    }
+}
+
+/// Run totals of `Metrics`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricsSummary {
+    pub generations: usize,
+    pub sampling: Duration,
+    pub eigendecomposition: Duration,
+    pub evaluation: Duration,
+    pub update: Duration,
+    pub allocations: Option<u64>,
+    pub peak_bytes: Option<usize>,
+    pub peak_resident_bytes: Option<usize>,
+}
+
+impl fmt::Display for MetricsSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gens = self.generations.max(1) as f64;
+        let total =
+            (self.sampling + self.eigendecomposition + self.evaluation + self.update).as_secs_f64();
+        let mib = |b: Option<usize>| match b {
+            Some(b) => format!("{:.1} MiB", b as f64 / (1024. * 1024.)),
+            None => "n/a".to_string(),
+        };
+        writeln!(f, "{} generations", self.generations)?;
+        writeln!(
+            f,
+            "{:<20} {:>10} {:>12} {:>6}",
+            "phase", "total s", "per gen ms", "%"
+        )?;
+        for (name, d) in [
+            ("sampling", self.sampling),
+            ("eigendecomposition", self.eigendecomposition),
+            ("evaluation", self.evaluation),
+            ("update", self.update),
+        ] {
+            let secs = d.as_secs_f64();
+            writeln!(
+                f,
+                "{:<20} {:>10.4} {:>12.4} {:>6.1}",
+                name,
+                secs,
+                secs * 1e3 / gens,
+                if total > 0. { 100. * secs / total } else { 0. }
+            )?;
+        }
+        match self.allocations {
+            Some(a) => writeln!(f, "allocations {} ({:.1} per gen)", a, a as f64 / gens)?,
+            None => writeln!(f, "allocations n/a")?,
+        }
+        write!(
+            f,
+            "peak heap {}, peak resident {}",
+            mib(self.peak_bytes),
+            mib(self.peak_resident_bytes)
+        )
+    }
+}
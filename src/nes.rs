@@ -0,0 +1,278 @@
+use crate::fitness::{Fitness, FitnessFunction, PopulationY};
+use crate::metrics::{Phase, PhaseTimer};
+use crate::params::CmaesParams;
+use crate::state::{CmaesState, CmaesStateLogic};
+use crate::strategy::{evals_spent, rank_population, tolerance_reached, CmaesAlgoOptimizer};
+use crate::utils::standard_normal;
+use anyhow::{ensure, Result};
+use nalgebra::{DMatrix, DVector, SymmetricEigen};
+
+/// Rank-based fitness shaping utilities, best first, summing to zero.
+///
+/// ```rust
+/// use haru_cmaes::nes::utilities;
+///
+/// let u = utilities(8);
+///
+/// assert!(u[0] > u[1]);
+/// assert!(u.sum().abs() < 1e-6);
+/// ```
+pub fn utilities(popsize: usize) -> DVector<f32> {
+    let lam = popsize as f32;
+    let raw = DVector::from_fn(popsize, |k, _| {
+        ((lam / 2. + 1.).ln() - ((k + 1) as f32).ln()).max(0.)
+    });
+    let sum = raw.sum();
+    raw.map(|u| u / sum - 1. / lam)
+}
+
+/// Samples `mean + sigma * A z` with `A` the symmetric square root of the covariance.
+fn sample(params: &CmaesParams, state: &mut CmaesState) -> Result<PopulationY> {
+    {
+        let _timer = PhaseTimer::start(Phase::Eigendecomposition);
+        state.prepare_ask(params)?;
+    }
+    let _timer = PhaseTimer::start(Phase::Sampling);
+    let z = standard_normal(params.popsize as usize, params.xstart.len());
+    state.z.copy_from(&z);
+    let a = sqrt_cov(state);
+    let y: DMatrix<f32> = DMatrix::from_rows(
+        &(z.map(|x| x * state.sigma) * a.transpose())
+            .row_iter()
+            .map(|row| row + state.mean.transpose())
+            .collect::<Vec<_>>(),
+    );
+    state.y.copy_from(&y);
+    Ok(PopulationY { y })
+}
+
+/// Symmetric square root of the covariance, from the last eigen decomposition.
+fn sqrt_cov(state: &CmaesState) -> DMatrix<f32> {
+    let sqrt_diag = DMatrix::from_diagonal(&state.eig_vals.map(|e| e.sqrt()));
+    &state.eig_vecs * sqrt_diag * state.eig_vecs.transpose()
+}
+
+/// Local coordinates `A^(-1) (y - mean) / sigma` of the sorted population.
+fn local_coords(state: &CmaesState, pop: &PopulationY) -> DMatrix<f32> {
+    DMatrix::from_rows(
+        &pop.y
+            .row_iter()
+            .map(|row| {
+                (&state.inv_sqrt * (row.transpose() - &state.mean) / state.sigma).transpose()
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Exponential natural evolution strategy (xNES).
+///
+/// The search distribution is carried in `CmaesState`: `mean`, `sigma`
+/// and `cov = B B^T` with `det(B) = 1`.
+///
+/// ```rust
+/// use haru_cmaes::fitness::MinOrMax;
+/// use haru_cmaes::nes::XnesAlgo;
+/// use haru_cmaes::objectives::SquareAndSum;
+/// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+/// use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+/// use haru_cmaes::strategy::CmaesAlgoOptimizer;
+///
+/// let params = CmaesParams::new()
+///     .and_then(|p| p.set_xstart(4, 1.0))
+///     .and_then(|p| p.set_num_gens(200))
+///     .unwrap();
+/// let xnes = XnesAlgo::new(params).unwrap();
+/// let state = CmaesState::init_state(&xnes.params).unwrap();
+/// let obj_func = SquareAndSum { obj_dim: 4, dir: MinOrMax::Min };
+/// let state = xnes.rollout_fold(state, obj_func).unwrap();
+///
+/// assert!(state.best_y_fit[0] < 1e-3);
+/// ```
+#[derive(Debug)]
+pub struct XnesAlgo {
+    pub params: CmaesParams,
+    pub eta_mu: f32,             // Learning rate for the mean
+    pub eta_sigma: f32,          // Learning rate for the step-size
+    pub eta_b: f32,              // Learning rate for the shape matrix
+    pub utilities: DVector<f32>, // Fitness shaping utilities
+}
+
+impl XnesAlgo {
+    /// Creates a new xNES instance with the default learning rates.
+    pub fn new(params: CmaesParams) -> Result<Self> {
+        ensure!(params.popsize >= 2, "Population size must be at least 2");
+        let n = params.n;
+        let eta = 0.6 * (3. + n.ln()) / (n * n.sqrt());
+        let utilities = utilities(params.popsize as usize);
+        Ok(Self {
+            params,
+            eta_mu: 1.0,
+            eta_sigma: eta,
+            eta_b: eta,
+            utilities,
+        })
+    }
+}
+
+impl CmaesAlgoOptimizer for XnesAlgo {
+    type NewPopulation = PopulationY;
+    type NewState = CmaesState;
+    type Done = bool;
+
+    fn params(&self) -> &CmaesParams {
+        &self.params
+    }
+
+    fn ask(&self, state: &mut CmaesState) -> Result<Self::NewPopulation> {
+        sample(&self.params, state)
+    }
+
+    fn tell(
+        &self,
+        mut state: CmaesState,
+        pop: &mut PopulationY,
+        fitness: &mut Fitness,
+    ) -> Result<Self::NewState> {
+        let _timer = PhaseTimer::start(Phase::Update);
+        state.g += 1;
+        state.evals_count += fitness.values.nrows() as i32;
+        rank_population(&mut state, pop, fitness);
+
+        // Natural gradients in local coordinates
+        let n = state.mean.len();
+        let z = local_coords(&state, pop);
+        let grad_delta: DVector<f32> = z.transpose() * &self.utilities;
+        let grad_m: DMatrix<f32> = z
+            .row_iter()
+            .zip(self.utilities.iter())
+            .fold(DMatrix::zeros(n, n), |acc, (row, &u)| {
+                acc + (row.transpose() * row - DMatrix::identity(n, n)) * u
+            });
+        let grad_sigma = grad_m.trace() / n as f32;
+        let grad_b = grad_m - DMatrix::identity(n, n) * grad_sigma;
+
+        // Update mean, step-size and shape
+        let a = sqrt_cov(&state);
+        state.mean += &a * grad_delta * (self.eta_mu * state.sigma);
+        state.sigma *= (self.eta_sigma / 2. * grad_sigma).exp();
+        let expm = SymmetricEigen::new(grad_b * (self.eta_b / 2.));
+        let expm = &expm.eigenvectors
+            * DMatrix::from_diagonal(&expm.eigenvalues.map(|e| e.exp()))
+            * expm.eigenvectors.transpose();
+        let a_new = a * expm;
+        state.cov = &a_new * a_new.transpose();
+
+        Ok(state)
+    }
+
+    fn is_done(&self, state: &CmaesState, step: i32) -> Result<Self::Done> {
+        Ok(tolerance_reached(state, self.params.tol, step) || evals_spent(state, &self.params))
+    }
+
+    fn rollout_fold(
+        &self,
+        state: CmaesState,
+        objective_function: impl FitnessFunction,
+    ) -> Result<Self::NewState> {
+        self.rollout_observed(state, objective_function, self.params.num_gens, &mut ())
+    }
+}
+
+/// Separable natural evolution strategy (SNES).
+///
+/// The search distribution is carried in `CmaesState`: `mean`, a fixed
+/// `sigma` and a diagonal `cov` holding the squared per-coordinate scales.
+///
+/// ```rust
+/// use haru_cmaes::fitness::MinOrMax;
+/// use haru_cmaes::nes::SnesAlgo;
+/// use haru_cmaes::objectives::SquareAndSum;
+/// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+/// use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+/// use haru_cmaes::strategy::CmaesAlgoOptimizer;
+///
+/// let params = CmaesParams::new()
+///     .and_then(|p| p.set_xstart(4, 1.0))
+///     .and_then(|p| p.set_num_gens(200))
+///     .unwrap();
+/// let snes = SnesAlgo::new(params).unwrap();
+/// let state = CmaesState::init_state(&snes.params).unwrap();
+/// let obj_func = SquareAndSum { obj_dim: 4, dir: MinOrMax::Min };
+/// let state = snes.rollout_fold(state, obj_func).unwrap();
+///
+/// assert!(state.best_y_fit[0] < 1e-3);
+/// ```
+#[derive(Debug)]
+pub struct SnesAlgo {
+    pub params: CmaesParams,
+    pub eta_mu: f32,             // Learning rate for the mean
+    pub eta_sigma: f32,          // Learning rate for the scales
+    pub utilities: DVector<f32>, // Fitness shaping utilities
+}
+
+impl SnesAlgo {
+    /// Creates a new SNES instance with the default learning rates.
+    pub fn new(mut params: CmaesParams) -> Result<Self> {
+        ensure!(params.popsize >= 2, "Population size must be at least 2");
+        params.only_diag = true;
+        let n = params.n;
+        let utilities = utilities(params.popsize as usize);
+        Ok(Self {
+            params,
+            eta_mu: 1.0,
+            eta_sigma: (3. + n.ln()) / (5. * n.sqrt()),
+            utilities,
+        })
+    }
+}
+
+impl CmaesAlgoOptimizer for SnesAlgo {
+    type NewPopulation = PopulationY;
+    type NewState = CmaesState;
+    type Done = bool;
+
+    fn params(&self) -> &CmaesParams {
+        &self.params
+    }
+
+    fn ask(&self, state: &mut CmaesState) -> Result<Self::NewPopulation> {
+        sample(&self.params, state)
+    }
+
+    fn tell(
+        &self,
+        mut state: CmaesState,
+        pop: &mut PopulationY,
+        fitness: &mut Fitness,
+    ) -> Result<Self::NewState> {
+        let _timer = PhaseTimer::start(Phase::Update);
+        state.g += 1;
+        state.evals_count += fitness.values.nrows() as i32;
+        rank_population(&mut state, pop, fitness);
+
+        // Per-coordinate gradients
+        let z = local_coords(&state, pop);
+        let grad_mu: DVector<f32> = z.transpose() * &self.utilities;
+        let grad_s: DVector<f32> = z.map(|x| x * x - 1.).transpose() * &self.utilities;
+
+        // Update mean and scales
+        let scales: DVector<f32> = state.cov.diagonal().map(|c| c.sqrt());
+        state.mean += scales.component_mul(&grad_mu) * (self.eta_mu * state.sigma);
+        let scales = scales.component_mul(&grad_s.map(|g| (self.eta_sigma / 2. * g).exp()));
+        state.cov = DMatrix::from_diagonal(&scales.map(|s| s * s));
+
+        Ok(state)
+    }
+
+    fn is_done(&self, state: &CmaesState, step: i32) -> Result<Self::Done> {
+        Ok(tolerance_reached(state, self.params.tol, step) || evals_spent(state, &self.params))
+    }
+
+    fn rollout_fold(
+        &self,
+        state: CmaesState,
+        objective_function: impl FitnessFunction,
+    ) -> Result<Self::NewState> {
+        self.rollout_observed(state, objective_function, self.params.num_gens, &mut ())
+    }
+}
@@ -1,4 +1,6 @@
+use crate::bbob::Bbob;
 use crate::fitness::{FitnessFunction, MinOrMax, PopulationY};
+use anyhow::{bail, ensure, Result};
 use nalgebra::{DMatrix, DVector};
 
 /// Implementation of the square and sum as fitness function.
@@ -250,7 +252,7 @@ impl ConstraintProblem {
         // Trivial optimization: reach a target value (pointy cone at 1.0)
         pop.y
             .row_iter()
-            .map(|row| (row.iter().map(|x| (x - self.target).abs().sqrt()).sum()))
+            .map(|row| row.iter().map(|x| (x - self.target).abs().sqrt()).sum())
             .collect::<Vec<f32>>()
             .into()
     }
@@ -413,3 +415,914 @@ impl DEAProblem {
         DVector::from_vec(res)
     }
 }
+
+/// A trait for test functions whose optimum is known in closed form.
+///
+/// Values refer to `cost`, i.e. before any sign change from `MinOrMax`.
+pub trait KnownOptimum {
+    fn optimum_x(&self) -> DVector<f32>;
+    fn optimum_f(&self) -> f32;
+}
+
+impl KnownOptimum for SquareAndSum {
+    fn optimum_x(&self) -> DVector<f32> {
+        DVector::zeros(self.obj_dim)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        0.0
+    }
+}
+
+impl KnownOptimum for Rastrigin {
+    fn optimum_x(&self) -> DVector<f32> {
+        DVector::zeros(self.obj_dim)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        0.0
+    }
+}
+
+impl KnownOptimum for XSquare {
+    fn optimum_x(&self) -> DVector<f32> {
+        DVector::zeros(self.obj_dim)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        0.0
+    }
+}
+
+/// Position `i / (n - 1)` of coordinate `i` in `[0, 1]`, zero in one dimension.
+fn ratio(i: usize, n: usize) -> f32 {
+    if n > 1 {
+        i as f32 / (n - 1) as f32
+    } else {
+        0.0
+    }
+}
+
+/// Implementation of the Rosenbrock function, minimum `0` at `(1, ..., 1)`.
+///
+/// ```rust
+/// use haru_cmaes::objectives::{KnownOptimum, Rosenbrock};
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::DMatrix;
+///
+/// let objective_function = Rosenbrock { obj_dim: 4, dir: MinOrMax::Min };
+/// let x = objective_function.optimum_x();
+/// let y = DMatrix::from_row_slice(1, 4, x.as_slice());
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+///
+/// // The known optimum is attained
+/// assert!((fitness.values[0] - objective_function.optimum_f()).abs() < 1e-6);
+/// ```
+pub struct Rosenbrock {
+    pub obj_dim: usize,
+    pub dir: MinOrMax,
+}
+
+impl FitnessFunction for Rosenbrock {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        pop.y
+            .row_iter()
+            .map(|row| {
+                (0..row.len().saturating_sub(1))
+                    .map(|i| 100.0 * (row[i + 1] - row[i].powi(2)).powi(2) + (1.0 - row[i]).powi(2))
+                    .sum()
+            })
+            .collect::<Vec<f32>>()
+            .into()
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.obj_dim
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        &self.dir
+    }
+}
+
+impl KnownOptimum for Rosenbrock {
+    fn optimum_x(&self) -> DVector<f32> {
+        DVector::from_element(self.obj_dim, 1.0)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        0.0
+    }
+}
+
+/// Implementation of the Ackley function, minimum `0` at the origin.
+///
+/// ```rust
+/// use haru_cmaes::objectives::{KnownOptimum, Ackley};
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::DMatrix;
+///
+/// let objective_function = Ackley { obj_dim: 4, dir: MinOrMax::Min };
+/// let x = objective_function.optimum_x();
+/// let y = DMatrix::from_row_slice(1, 4, x.as_slice());
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+///
+/// // The known optimum is attained
+/// assert!((fitness.values[0] - objective_function.optimum_f()).abs() < 1e-5);
+/// ```
+pub struct Ackley {
+    pub obj_dim: usize,
+    pub dir: MinOrMax,
+}
+
+impl FitnessFunction for Ackley {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        let pi = std::f32::consts::PI;
+        pop.y
+            .row_iter()
+            .map(|row| {
+                let n = row.len() as f32;
+                let sq = row.iter().map(|x| x * x).sum::<f32>() / n;
+                let cos = row.iter().map(|x| (2.0 * pi * x).cos()).sum::<f32>() / n;
+                -20.0 * (-0.2 * sq.sqrt()).exp() - cos.exp() + 20.0 + std::f32::consts::E
+            })
+            .collect::<Vec<f32>>()
+            .into()
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.obj_dim
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        &self.dir
+    }
+}
+
+impl KnownOptimum for Ackley {
+    fn optimum_x(&self) -> DVector<f32> {
+        DVector::zeros(self.obj_dim)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        0.0
+    }
+}
+
+/// Implementation of the Griewank function, minimum `0` at the origin.
+///
+/// ```rust
+/// use haru_cmaes::objectives::{KnownOptimum, Griewank};
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::DMatrix;
+///
+/// let objective_function = Griewank { obj_dim: 4, dir: MinOrMax::Min };
+/// let x = objective_function.optimum_x();
+/// let y = DMatrix::from_row_slice(1, 4, x.as_slice());
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+///
+/// // The known optimum is attained
+/// assert!((fitness.values[0] - objective_function.optimum_f()).abs() < 1e-6);
+/// ```
+pub struct Griewank {
+    pub obj_dim: usize,
+    pub dir: MinOrMax,
+}
+
+impl FitnessFunction for Griewank {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        pop.y
+            .row_iter()
+            .map(|row| {
+                let sum = row.iter().map(|x| x * x).sum::<f32>() / 4000.0;
+                let prod = row
+                    .iter()
+                    .enumerate()
+                    .map(|(i, x)| (x / ((i + 1) as f32).sqrt()).cos())
+                    .product::<f32>();
+                1.0 + sum - prod
+            })
+            .collect::<Vec<f32>>()
+            .into()
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.obj_dim
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        &self.dir
+    }
+}
+
+impl KnownOptimum for Griewank {
+    fn optimum_x(&self) -> DVector<f32> {
+        DVector::zeros(self.obj_dim)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        0.0
+    }
+}
+
+/// Implementation of the Schwefel 1.2 (double sum) function, minimum `0` at the origin.
+///
+/// ```rust
+/// use haru_cmaes::objectives::{KnownOptimum, Schwefel12};
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::DMatrix;
+///
+/// let objective_function = Schwefel12 { obj_dim: 4, dir: MinOrMax::Min };
+/// let x = objective_function.optimum_x();
+/// let y = DMatrix::from_row_slice(1, 4, x.as_slice());
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+///
+/// // The known optimum is attained
+/// assert!((fitness.values[0] - objective_function.optimum_f()).abs() < 1e-6);
+/// ```
+pub struct Schwefel12 {
+    pub obj_dim: usize,
+    pub dir: MinOrMax,
+}
+
+impl FitnessFunction for Schwefel12 {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        pop.y
+            .row_iter()
+            .map(|row| {
+                row.iter()
+                    .scan(0.0, |partial, x| {
+                        *partial += x;
+                        Some(*partial * *partial)
+                    })
+                    .sum()
+            })
+            .collect::<Vec<f32>>()
+            .into()
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.obj_dim
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        &self.dir
+    }
+}
+
+impl KnownOptimum for Schwefel12 {
+    fn optimum_x(&self) -> DVector<f32> {
+        DVector::zeros(self.obj_dim)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        0.0
+    }
+}
+
+/// Implementation of the Schwefel 2.26 function, minimum `0` at `(420.9687, ..., 420.9687)`.
+///
+/// The global minimum is at the edge of the usual domain `[-500, 500]`,
+/// far from the next best local minima. In `f32` the value at the optimum
+/// is only zero up to about `1e-4` per dimension.
+///
+/// ```rust
+/// use haru_cmaes::objectives::{KnownOptimum, Schwefel226};
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::DMatrix;
+///
+/// let objective_function = Schwefel226 { obj_dim: 4, dir: MinOrMax::Min };
+/// let x = objective_function.optimum_x();
+/// let y = DMatrix::from_row_slice(1, 4, x.as_slice());
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+///
+/// // The known optimum is attained
+/// assert!((fitness.values[0] - objective_function.optimum_f()).abs() < 1e-3);
+/// ```
+pub struct Schwefel226 {
+    pub obj_dim: usize,
+    pub dir: MinOrMax,
+}
+
+impl FitnessFunction for Schwefel226 {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        pop.y
+            .row_iter()
+            .map(|row| {
+                418.98288 * row.len() as f32
+                    - row.iter().map(|x| x * x.abs().sqrt().sin()).sum::<f32>()
+            })
+            .collect::<Vec<f32>>()
+            .into()
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.obj_dim
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        &self.dir
+    }
+}
+
+impl KnownOptimum for Schwefel226 {
+    fn optimum_x(&self) -> DVector<f32> {
+        DVector::from_element(self.obj_dim, 420.96875)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        0.0
+    }
+}
+
+/// Implementation of the Levy function, minimum `0` at `(1, ..., 1)`.
+///
+/// ```rust
+/// use haru_cmaes::objectives::{KnownOptimum, Levy};
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::DMatrix;
+///
+/// let objective_function = Levy { obj_dim: 4, dir: MinOrMax::Min };
+/// let x = objective_function.optimum_x();
+/// let y = DMatrix::from_row_slice(1, 4, x.as_slice());
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+///
+/// // The known optimum is attained
+/// assert!((fitness.values[0] - objective_function.optimum_f()).abs() < 1e-6);
+/// ```
+pub struct Levy {
+    pub obj_dim: usize,
+    pub dir: MinOrMax,
+}
+
+impl FitnessFunction for Levy {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        let pi = std::f32::consts::PI;
+        pop.y
+            .row_iter()
+            .map(|row| {
+                let w: Vec<f32> = row.iter().map(|x| 1.0 + (x - 1.0) / 4.0).collect();
+                let last = w[w.len() - 1];
+                (pi * w[0]).sin().powi(2)
+                    + w[..w.len() - 1]
+                        .iter()
+                        .map(|wi| (wi - 1.0).powi(2) * (1.0 + 10.0 * (pi * wi + 1.0).sin().powi(2)))
+                        .sum::<f32>()
+                    + (last - 1.0).powi(2) * (1.0 + (2.0 * pi * last).sin().powi(2))
+            })
+            .collect::<Vec<f32>>()
+            .into()
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.obj_dim
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        &self.dir
+    }
+}
+
+impl KnownOptimum for Levy {
+    fn optimum_x(&self) -> DVector<f32> {
+        DVector::from_element(self.obj_dim, 1.0)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        0.0
+    }
+}
+
+/// Implementation of the Styblinski-Tang function, minimum `-39.16617 n` at `(-2.903534, ..., -2.903534)`.
+///
+/// ```rust
+/// use haru_cmaes::objectives::{KnownOptimum, StyblinskiTang};
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::DMatrix;
+///
+/// let objective_function = StyblinskiTang { obj_dim: 4, dir: MinOrMax::Min };
+/// let x = objective_function.optimum_x();
+/// let y = DMatrix::from_row_slice(1, 4, x.as_slice());
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+///
+/// // The known optimum is attained
+/// assert!((fitness.values[0] - objective_function.optimum_f()).abs() < 1e-4);
+/// ```
+pub struct StyblinskiTang {
+    pub obj_dim: usize,
+    pub dir: MinOrMax,
+}
+
+impl FitnessFunction for StyblinskiTang {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        pop.y
+            .row_iter()
+            .map(|row| {
+                row.iter()
+                    .map(|x| x.powi(4) - 16.0 * x.powi(2) + 5.0 * x)
+                    .sum::<f32>()
+                    / 2.0
+            })
+            .collect::<Vec<f32>>()
+            .into()
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.obj_dim
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        &self.dir
+    }
+}
+
+impl KnownOptimum for StyblinskiTang {
+    fn optimum_x(&self) -> DVector<f32> {
+        DVector::from_element(self.obj_dim, -2.903534)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        -39.16617 * self.obj_dim as f32
+    }
+}
+
+/// Implementation of the Zakharov function, minimum `0` at the origin.
+///
+/// ```rust
+/// use haru_cmaes::objectives::{KnownOptimum, Zakharov};
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::DMatrix;
+///
+/// let objective_function = Zakharov { obj_dim: 4, dir: MinOrMax::Min };
+/// let x = objective_function.optimum_x();
+/// let y = DMatrix::from_row_slice(1, 4, x.as_slice());
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+///
+/// // The known optimum is attained
+/// assert!((fitness.values[0] - objective_function.optimum_f()).abs() < 1e-6);
+/// ```
+pub struct Zakharov {
+    pub obj_dim: usize,
+    pub dir: MinOrMax,
+}
+
+impl FitnessFunction for Zakharov {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        pop.y
+            .row_iter()
+            .map(|row| {
+                let sq = row.iter().map(|x| x * x).sum::<f32>();
+                let lin = row
+                    .iter()
+                    .enumerate()
+                    .map(|(i, x)| 0.5 * (i + 1) as f32 * x)
+                    .sum::<f32>();
+                sq + lin.powi(2) + lin.powi(4)
+            })
+            .collect::<Vec<f32>>()
+            .into()
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.obj_dim
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        &self.dir
+    }
+}
+
+impl KnownOptimum for Zakharov {
+    fn optimum_x(&self) -> DVector<f32> {
+        DVector::zeros(self.obj_dim)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        0.0
+    }
+}
+
+/// Implementation of the Michalewicz function on `[0, pi]^n` with steepness `m` (usually 10).
+///
+/// The function is separable, so the optimum is found coordinate-wise by a
+/// one-dimensional search over the peaks of each term, e.g. `-1.8013` in 2
+/// and `-4.687658` in 5 dimensions.
+///
+/// ```rust
+/// use haru_cmaes::objectives::{KnownOptimum, Michalewicz};
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::DMatrix;
+///
+/// let objective_function = Michalewicz { obj_dim: 5, dir: MinOrMax::Min, m: 10.0 };
+/// let x = objective_function.optimum_x();
+/// let y = DMatrix::from_row_slice(1, 5, x.as_slice());
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+///
+/// // The known optimum is attained
+/// assert!((fitness.values[0] - objective_function.optimum_f()).abs() < 1e-5);
+/// assert!((objective_function.optimum_f() + 4.687658).abs() < 1e-4);
+/// ```
+pub struct Michalewicz {
+    pub obj_dim: usize,
+    pub dir: MinOrMax,
+    pub m: f32,
+}
+
+impl FitnessFunction for Michalewicz {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        pop.y
+            .row_iter()
+            .map(|row| {
+                -row.iter()
+                    .enumerate()
+                    .map(|(i, &x)| self.term(i, x as f64) as f32)
+                    .sum::<f32>()
+            })
+            .collect::<Vec<f32>>()
+            .into()
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.obj_dim
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        &self.dir
+    }
+}
+
+impl Michalewicz {
+    /// Contribution `sin(x) sin(i x^2 / pi)^(2m)` of coordinate `i` (from 0).
+    fn term(&self, i: usize, x: f64) -> f64 {
+        let pi = std::f64::consts::PI;
+        x.sin()
+            * ((i + 1) as f64 * x * x / pi)
+                .sin()
+                .powf(2.0 * self.m as f64)
+    }
+}
+
+impl Michalewicz {
+    /// Maximizer of `term(i, .)` on `[0, pi]` and its value.
+    ///
+    /// `sin(i x^2 / pi)^(2m)` has one peak between consecutive zeros at
+    /// `x^2 = k pi^2 / i`; a golden section search on each of these brackets
+    /// gives the candidates, the best of which is kept.
+    fn optimum_coordinate(&self, i: usize) -> (f64, f64) {
+        let pi = std::f64::consts::PI;
+        let ratio = (5f64.sqrt() - 1.0) / 2.0;
+        let zero = |k: usize| (k as f64 * pi * pi / (i + 1) as f64).sqrt().min(pi);
+        (0..=i)
+            .map(|k| {
+                let (mut lo, mut hi) = (zero(k), zero(k + 1));
+                for _ in 0..80 {
+                    let (a, b) = (hi - ratio * (hi - lo), lo + ratio * (hi - lo));
+                    if self.term(i, a) > self.term(i, b) {
+                        hi = b;
+                    } else {
+                        lo = a;
+                    }
+                }
+                let x = (lo + hi) / 2.0;
+                (x, self.term(i, x))
+            })
+            .fold(
+                (0.0, f64::MIN),
+                |best, c| if c.1 > best.1 { c } else { best },
+            )
+    }
+}
+
+impl KnownOptimum for Michalewicz {
+    fn optimum_x(&self) -> DVector<f32> {
+        DVector::from_fn(self.obj_dim, |i, _| self.optimum_coordinate(i).0 as f32)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        -(0..self.obj_dim)
+            .map(|i| self.optimum_coordinate(i).1)
+            .sum::<f64>() as f32
+    }
+}
+
+/// Implementation of the Bent Cigar function, minimum `0` at the origin.
+///
+/// ```rust
+/// use haru_cmaes::objectives::{KnownOptimum, BentCigar};
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::DMatrix;
+///
+/// let objective_function = BentCigar { obj_dim: 4, dir: MinOrMax::Min };
+/// let x = objective_function.optimum_x();
+/// let y = DMatrix::from_row_slice(1, 4, x.as_slice());
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+///
+/// // The known optimum is attained
+/// assert!((fitness.values[0] - objective_function.optimum_f()).abs() < 1e-6);
+/// ```
+pub struct BentCigar {
+    pub obj_dim: usize,
+    pub dir: MinOrMax,
+}
+
+impl FitnessFunction for BentCigar {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        pop.y
+            .row_iter()
+            .map(|row| row[0].powi(2) + 1e6 * row.iter().skip(1).map(|x| x * x).sum::<f32>())
+            .collect::<Vec<f32>>()
+            .into()
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.obj_dim
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        &self.dir
+    }
+}
+
+impl KnownOptimum for BentCigar {
+    fn optimum_x(&self) -> DVector<f32> {
+        DVector::zeros(self.obj_dim)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        0.0
+    }
+}
+
+/// Implementation of the Ellipsoid function with condition `1e6`, minimum `0` at the origin.
+///
+/// ```rust
+/// use haru_cmaes::objectives::{KnownOptimum, Ellipsoid};
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::DMatrix;
+///
+/// let objective_function = Ellipsoid { obj_dim: 4, dir: MinOrMax::Min };
+/// let x = objective_function.optimum_x();
+/// let y = DMatrix::from_row_slice(1, 4, x.as_slice());
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+///
+/// // The known optimum is attained
+/// assert!((fitness.values[0] - objective_function.optimum_f()).abs() < 1e-6);
+/// ```
+pub struct Ellipsoid {
+    pub obj_dim: usize,
+    pub dir: MinOrMax,
+}
+
+impl FitnessFunction for Ellipsoid {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        pop.y
+            .row_iter()
+            .map(|row| {
+                row.iter()
+                    .enumerate()
+                    .map(|(i, x)| 1e6f32.powf(ratio(i, row.len())) * x * x)
+                    .sum()
+            })
+            .collect::<Vec<f32>>()
+            .into()
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.obj_dim
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        &self.dir
+    }
+}
+
+impl KnownOptimum for Ellipsoid {
+    fn optimum_x(&self) -> DVector<f32> {
+        DVector::zeros(self.obj_dim)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        0.0
+    }
+}
+
+/// Implementation of the Different Powers function, minimum `0` at the origin.
+///
+/// ```rust
+/// use haru_cmaes::objectives::{KnownOptimum, DifferentPowers};
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::DMatrix;
+///
+/// let objective_function = DifferentPowers { obj_dim: 4, dir: MinOrMax::Min };
+/// let x = objective_function.optimum_x();
+/// let y = DMatrix::from_row_slice(1, 4, x.as_slice());
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+///
+/// // The known optimum is attained
+/// assert!((fitness.values[0] - objective_function.optimum_f()).abs() < 1e-6);
+/// ```
+pub struct DifferentPowers {
+    pub obj_dim: usize,
+    pub dir: MinOrMax,
+}
+
+impl FitnessFunction for DifferentPowers {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        pop.y
+            .row_iter()
+            .map(|row| {
+                row.iter()
+                    .enumerate()
+                    .map(|(i, x)| x.abs().powf(2.0 + 4.0 * ratio(i, row.len())))
+                    .sum::<f32>()
+                    .sqrt()
+            })
+            .collect::<Vec<f32>>()
+            .into()
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.obj_dim
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        &self.dir
+    }
+}
+
+impl KnownOptimum for DifferentPowers {
+    fn optimum_x(&self) -> DVector<f32> {
+        DVector::zeros(self.obj_dim)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        0.0
+    }
+}
+
+/// Implementation of the Sharp Ridge function, minimum `0` at the origin.
+///
+/// ```rust
+/// use haru_cmaes::objectives::{KnownOptimum, SharpRidge};
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::DMatrix;
+///
+/// let objective_function = SharpRidge { obj_dim: 4, dir: MinOrMax::Min };
+/// let x = objective_function.optimum_x();
+/// let y = DMatrix::from_row_slice(1, 4, x.as_slice());
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+///
+/// // The known optimum is attained
+/// assert!((fitness.values[0] - objective_function.optimum_f()).abs() < 1e-6);
+/// ```
+pub struct SharpRidge {
+    pub obj_dim: usize,
+    pub dir: MinOrMax,
+}
+
+impl FitnessFunction for SharpRidge {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        pop.y
+            .row_iter()
+            .map(|row| {
+                row[0].powi(2) + 100.0 * row.iter().skip(1).map(|x| x * x).sum::<f32>().sqrt()
+            })
+            .collect::<Vec<f32>>()
+            .into()
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.obj_dim
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        &self.dir
+    }
+}
+
+impl KnownOptimum for SharpRidge {
+    fn optimum_x(&self) -> DVector<f32> {
+        DVector::zeros(self.obj_dim)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        0.0
+    }
+}
+
+/// Names accepted by `by_name`, besides `bbob-F` for BBOB function `F`.
+pub const NAMES: [&str; 15] = [
+    "sphere",
+    "ellipsoid",
+    "rosenbrock",
+    "rastrigin",
+    "ackley",
+    "griewank",
+    "levy",
+    "zakharov",
+    "schwefel12",
+    "schwefel226",
+    "styblinski-tang",
+    "bent-cigar",
+    "different-powers",
+    "sharp-ridge",
+    "michalewicz",
+];
+
+/// Built-in objective of dimension `obj_dim` by name, minimized.
+///
+/// `bbob-F` is BBOB function `F`, instance 1, and `michalewicz` uses the
+/// standard steepness `m = 10`.
+///
+/// ```rust
+/// use haru_cmaes::objectives::by_name;
+///
+/// let objective = by_name("rosenbrock", 4).unwrap();
+/// assert_eq!(objective.cost_dim(), 4);
+/// assert!(by_name("bbob-8", 4).is_ok());
+/// assert!(by_name("bbob-25", 4).is_err());
+/// assert!(by_name("unknown", 4).is_err());
+/// assert!(by_name("rosenbrock", 0).is_err());
+/// ```
+pub fn by_name(name: &str, obj_dim: usize) -> Result<Box<dyn FitnessFunction>> {
+    ensure!(obj_dim >= 1, "Objective dimension must be at least 1");
+    let dir = MinOrMax::Min;
+    let f: Box<dyn FitnessFunction> = match name {
+        "sphere" => Box::new(SquareAndSum { obj_dim, dir }),
+        "ellipsoid" => Box::new(Ellipsoid { obj_dim, dir }),
+        "rosenbrock" => Box::new(Rosenbrock { obj_dim, dir }),
+        "rastrigin" => Box::new(Rastrigin { obj_dim, dir }),
+        "ackley" => Box::new(Ackley { obj_dim, dir }),
+        "griewank" => Box::new(Griewank { obj_dim, dir }),
+        "levy" => Box::new(Levy { obj_dim, dir }),
+        "zakharov" => Box::new(Zakharov { obj_dim, dir }),
+        "schwefel12" => Box::new(Schwefel12 { obj_dim, dir }),
+        "schwefel226" => Box::new(Schwefel226 { obj_dim, dir }),
+        "styblinski-tang" => Box::new(StyblinskiTang { obj_dim, dir }),
+        "bent-cigar" => Box::new(BentCigar { obj_dim, dir }),
+        "different-powers" => Box::new(DifferentPowers { obj_dim, dir }),
+        "sharp-ridge" => Box::new(SharpRidge { obj_dim, dir }),
+        "michalewicz" => Box::new(Michalewicz {
+            obj_dim,
+            dir,
+            m: 10.0,
+        }),
+        name => match name.strip_prefix("bbob-").map(str::parse::<usize>) {
+            Some(Ok(function)) => Box::new(Bbob::new(function, obj_dim, 1)?),
+            _ => bail!("Unknown objective '{}'", name),
+        },
+    };
+    Ok(f)
+}
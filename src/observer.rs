@@ -0,0 +1,233 @@
+use crate::datalogger::CmaDataLogger;
+use crate::fitness::{Fitness, PopulationY};
+use crate::metrics::Metrics;
+use crate::state::CmaesState;
+use anyhow::Result;
+
+/// Whether a run goes on after a hook.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    Continue,
+    Stop,
+}
+
+/// Why a run ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Termination {
+    MaxGenerations, // Generation limit reached
+    Tolerance,      // `is_done` reported convergence
+    Stopped,        // An observer returned `Control::Stop`
+    StepSize,       // Search steps shrank below `optimize::MIN_STEP`
+    MaxEvaluations, // Evaluation budget spent
+    MaxTime,        // Wall-clock budget spent
+}
+
+/// Hooks called by the run drivers around each step of a generation.
+///
+/// All hooks get read-only access to the state and default to doing
+/// nothing. The per-generation hooks can end the run early by returning
+/// `Control::Stop`; errors abort it.
+///
+/// ```rust
+/// use haru_cmaes::fitness::MinOrMax;
+/// use haru_cmaes::objectives::SquareAndSum;
+/// use haru_cmaes::observer::{Control, Observer, Termination};
+/// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+/// use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+/// use haru_cmaes::strategy::{CmaesAlgo, CmaesAlgoOptimizer};
+/// use haru_cmaes::fitness::{Fitness, PopulationY};
+/// use anyhow::Result;
+///
+/// // Stops once the best fitness is below a target
+/// struct Target {
+///     target: f32,
+///     reason: Option<Termination>,
+/// }
+///
+/// impl Observer for Target {
+///     fn after_tell(
+///         &mut self,
+///         state: &CmaesState,
+///         _pop: &PopulationY,
+///         _fitness: &Fitness,
+///     ) -> Result<Control> {
+///         Ok(if state.best_y_fit[0] < self.target { Control::Stop } else { Control::Continue })
+///     }
+///
+///     fn on_termination(&mut self, _state: &CmaesState, reason: Termination) -> Result<()> {
+///         self.reason = Some(reason);
+///         Ok(())
+///     }
+/// }
+///
+/// let params = CmaesParams::new().and_then(|p| p.set_num_gens(500)).unwrap();
+/// let cmaes = CmaesAlgo::new(params).unwrap();
+/// let state = CmaesState::init_state(&cmaes.params).unwrap();
+/// let obj_func = SquareAndSum { obj_dim: 6, dir: MinOrMax::Min };
+/// let mut observer = Target { target: 1e-3, reason: None };
+/// let state = cmaes.rollout_observed(state, obj_func, 500, &mut observer).unwrap();
+///
+/// assert_eq!(observer.reason, Some(Termination::Stopped));
+/// assert!(state.best_y_fit[0] < 1e-3);
+/// assert!(state.g < 500);
+/// ```
+pub trait Observer {
+    /// After `ask`, with the candidates to evaluate.
+    fn after_ask(&mut self, _state: &CmaesState, _pop: &PopulationY) -> Result<Control> {
+        Ok(Control::Continue)
+    }
+
+    /// After evaluation, with the unsorted candidates and their fitness.
+    fn after_evaluate(
+        &mut self,
+        _state: &CmaesState,
+        _pop: &PopulationY,
+        _fitness: &Fitness,
+    ) -> Result<Control> {
+        Ok(Control::Continue)
+    }
+
+    /// After `tell`, with the population and fitness sorted best first.
+    fn after_tell(
+        &mut self,
+        _state: &CmaesState,
+        _pop: &PopulationY,
+        _fitness: &Fitness,
+    ) -> Result<Control> {
+        Ok(Control::Continue)
+    }
+
+    /// When a driver restarts from a new state, `restart` counting from 1.
+    fn on_restart(&mut self, _state: &CmaesState, _restart: usize) -> Result<()> {
+        Ok(())
+    }
+
+    /// Once per run, with its final state.
+    fn on_termination(&mut self, _state: &CmaesState, _reason: Termination) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// No-op observer.
+impl Observer for () {}
+
+impl<O: Observer + ?Sized> Observer for &mut O {
+    fn after_ask(&mut self, state: &CmaesState, pop: &PopulationY) -> Result<Control> {
+        (**self).after_ask(state, pop)
+    }
+
+    fn after_evaluate(
+        &mut self,
+        state: &CmaesState,
+        pop: &PopulationY,
+        fitness: &Fitness,
+    ) -> Result<Control> {
+        (**self).after_evaluate(state, pop, fitness)
+    }
+
+    fn after_tell(
+        &mut self,
+        state: &CmaesState,
+        pop: &PopulationY,
+        fitness: &Fitness,
+    ) -> Result<Control> {
+        (**self).after_tell(state, pop, fitness)
+    }
+
+    fn on_restart(&mut self, state: &CmaesState, restart: usize) -> Result<()> {
+        (**self).on_restart(state, restart)
+    }
+
+    fn on_termination(&mut self, state: &CmaesState, reason: Termination) -> Result<()> {
+        (**self).on_termination(state, reason)
+    }
+}
+
+/// Several observers, called in order; any of them can stop the run.
+impl Observer for Vec<Box<dyn Observer>> {
+    fn after_ask(&mut self, state: &CmaesState, pop: &PopulationY) -> Result<Control> {
+        self.iter_mut().try_fold(Control::Continue, |control, o| {
+            Ok(combine(control, o.after_ask(state, pop)?))
+        })
+    }
+
+    fn after_evaluate(
+        &mut self,
+        state: &CmaesState,
+        pop: &PopulationY,
+        fitness: &Fitness,
+    ) -> Result<Control> {
+        self.iter_mut().try_fold(Control::Continue, |control, o| {
+            Ok(combine(control, o.after_evaluate(state, pop, fitness)?))
+        })
+    }
+
+    fn after_tell(
+        &mut self,
+        state: &CmaesState,
+        pop: &PopulationY,
+        fitness: &Fitness,
+    ) -> Result<Control> {
+        self.iter_mut().try_fold(Control::Continue, |control, o| {
+            Ok(combine(control, o.after_tell(state, pop, fitness)?))
+        })
+    }
+
+    fn on_restart(&mut self, state: &CmaesState, restart: usize) -> Result<()> {
+        self.iter_mut()
+            .try_for_each(|o| o.on_restart(state, restart))
+    }
+
+    fn on_termination(&mut self, state: &CmaesState, reason: Termination) -> Result<()> {
+        self.iter_mut()
+            .try_for_each(|o| o.on_termination(state, reason))
+    }
+}
+
+fn combine(a: Control, b: Control) -> Control {
+    if a == Control::Stop || b == Control::Stop {
+        Control::Stop
+    } else {
+        Control::Continue
+    }
+}
+
+/// Writes the pycma data files after every `tell`.
+impl Observer for CmaDataLogger {
+    fn after_tell(
+        &mut self,
+        state: &CmaesState,
+        pop: &PopulationY,
+        fitness: &Fitness,
+    ) -> Result<Control> {
+        self.add(state, pop, fitness)?;
+        Ok(Control::Continue)
+    }
+}
+
+/// Records the timings and memory use of every generation.
+impl Observer for Metrics {
+    fn after_tell(
+        &mut self,
+        state: &CmaesState,
+        _pop: &PopulationY,
+        _fitness: &Fitness,
+    ) -> Result<Control> {
+        self.record(state);
+        Ok(Control::Continue)
+    }
+}
+
+/// Records every generation for plotting.
+#[cfg(feature = "plot")]
+impl Observer for crate::plot::History {
+    fn after_tell(
+        &mut self,
+        state: &CmaesState,
+        _pop: &PopulationY,
+        fitness: &Fitness,
+    ) -> Result<Control> {
+        self.record(state, fitness);
+        Ok(Control::Continue)
+    }
+}
@@ -0,0 +1,474 @@
+use crate::fitness::{FitnessFunction, MinOrMax};
+use crate::observer::{Control, Observer, Termination};
+use crate::params::{CmaesParams, CmaesParamsValidator, StepSizeRule};
+use crate::state::{CmaesState, CmaesStateLogic};
+use crate::strategy::{Budget, CmaesAlgo, CmaesAlgoOptimizer};
+use anyhow::{ensure, Error, Result};
+use nalgebra::DVector;
+use std::time::{Duration, Instant};
+
+/// Smallest search step, `sigma * sqrt(max eigenvalue)`, worth sampling.
+pub const MIN_STEP: f32 = 1e-12;
+
+/// Settings of `optimize`, with defaults for the rest.
+#[derive(Debug, Clone)]
+pub struct OptimizeOptions {
+    pub xstart: Option<Vec<f32>>,   // Initial mean, defaults to the origin
+    pub sigma: f32,                 // Initial step-size
+    pub popsize: Option<i32>,       // Defaults to 4 + 3 ln(n)
+    pub max_gens: i32,              // Generations per run
+    pub max_evals: Option<i32>,     // Evaluations over all runs
+    pub max_time: Option<Duration>, // Wall-clock time over all runs
+    pub tol: f32,                   // Tolerance of `is_done`
+    pub restarts: usize,            // Restarts after convergence, doubling the population (IPOP)
+    pub step_size_rule: StepSizeRule,
+    pub only_diag: bool,
+    pub history: bool, // Whether to keep a snapshot of every generation
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        Self {
+            xstart: None,
+            sigma: 0.75,
+            popsize: None,
+            max_gens: 1000,
+            max_evals: None,
+            max_time: None,
+            tol: 1e-12,
+            restarts: 0,
+            step_size_rule: StepSizeRule::Csa,
+            only_diag: false,
+            history: false,
+        }
+    }
+}
+
+impl OptimizeOptions {
+    /// Default options.
+    ///
+    /// ```rust
+    /// use haru_cmaes::optimize::OptimizeOptions;
+    ///
+    /// let options = OptimizeOptions::new()
+    ///     .set_sigma(0.5)
+    ///     .and_then(|o| o.set_popsize(20))
+    ///     .and_then(|o| o.set_restarts(2));
+    ///
+    /// assert!(options.is_ok());
+    /// assert!(OptimizeOptions::new().set_sigma(-1.0).is_err());
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the initial mean.
+    pub fn set_xstart(mut self, xstart: Vec<f32>) -> Result<Self> {
+        ensure!(!xstart.is_empty(), "Initial mean must not be empty");
+        self.xstart = Some(xstart);
+        Ok(self)
+    }
+
+    /// Sets the initial step-size.
+    pub fn set_sigma(mut self, sigma: f32) -> Result<Self> {
+        ensure!(sigma > 0.0, "Sigma must be positive");
+        self.sigma = sigma;
+        Ok(self)
+    }
+
+    /// Sets the population size of the first run.
+    pub fn set_popsize(mut self, popsize: i32) -> Result<Self> {
+        ensure!(popsize >= 2, "Population size must be at least 2");
+        self.popsize = Some(popsize);
+        Ok(self)
+    }
+
+    /// Sets the generation limit of each run.
+    pub fn set_max_gens(mut self, max_gens: i32) -> Result<Self> {
+        ensure!(max_gens >= 1, "Generation limit must be at least 1");
+        self.max_gens = max_gens;
+        Ok(self)
+    }
+
+    /// Sets the evaluation budget over all runs, the last generation truncated to fit.
+    pub fn set_max_evals(mut self, max_evals: i32) -> Result<Self> {
+        ensure!(max_evals >= 1, "Evaluation budget must be at least 1");
+        self.max_evals = Some(max_evals);
+        Ok(self)
+    }
+
+    /// Sets the wall-clock budget over all runs, checked between generations.
+    pub fn set_max_time(mut self, max_time: Duration) -> Result<Self> {
+        ensure!(!max_time.is_zero(), "Time budget must be positive");
+        self.max_time = Some(max_time);
+        Ok(self)
+    }
+
+    /// Sets the tolerance of `is_done`.
+    pub fn set_tol(mut self, tol: f32) -> Result<Self> {
+        ensure!(tol >= 0.0, "Tolerance must not be negative");
+        self.tol = tol;
+        Ok(self)
+    }
+
+    /// Sets how many times a converged run restarts with twice the population.
+    pub fn set_restarts(mut self, restarts: usize) -> Result<Self> {
+        self.restarts = restarts;
+        Ok(self)
+    }
+
+    /// Sets the step-size adaptation rule.
+    pub fn set_step_size_rule(mut self, rule: StepSizeRule) -> Result<Self> {
+        self.step_size_rule = rule;
+        Ok(self)
+    }
+
+    /// Sets enforce covariance sparsity.
+    pub fn set_only_diag(mut self, only_diag: bool) -> Result<Self> {
+        self.only_diag = only_diag;
+        Ok(self)
+    }
+
+    /// Sets whether to keep a snapshot of every generation.
+    pub fn set_history(mut self, history: bool) -> Result<Self> {
+        self.history = history;
+        Ok(self)
+    }
+
+    /// Parameters of a run with `popsize` candidates.
+    fn params(&self, xstart: &[f32], popsize: i32) -> Result<CmaesParams> {
+        let mut params = CmaesParams::new()
+            .and_then(|p| p.set_popsize(popsize))
+            .and_then(|p| p.set_xstart(xstart.len(), 0.0))
+            .and_then(|p| p.set_sigma(self.sigma))
+            .and_then(|p| p.set_num_gens(self.max_gens))
+            .and_then(|p| p.set_tol(self.tol))
+            .and_then(|p| p.set_only_diag(self.only_diag))
+            .and_then(|p| p.set_step_size_rule(self.step_size_rule))?;
+        params.xstart = xstart.to_vec();
+        Ok(params)
+    }
+}
+
+/// Progress after one generation, objective values in the objective's sign.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub g: i32,
+    pub evals: i32,
+    pub best_x: DVector<f32>, // Best candidate so far
+    pub best_f: f32,          // Best objective value so far
+    pub sigma: f32,
+    pub mean: DVector<f32>,
+}
+
+impl Snapshot {
+    /// Snapshot of `state`, with `sign` undoing the minimization.
+    pub fn new(state: &CmaesState, sign: f32) -> Self {
+        Self {
+            g: state.g,
+            evals: state.evals_count,
+            best_x: state.best_y.clone(),
+            best_f: sign * state.best_y_fit[0],
+            sigma: state.sigma,
+            mean: state.mean.clone(),
+        }
+    }
+}
+
+/// Outcome of `optimize`, objective values in the objective's sign.
+///
+/// ```rust
+/// use haru_cmaes::fitness::MinOrMax;
+/// use haru_cmaes::objectives::Rastrigin;
+/// use haru_cmaes::observer::Termination;
+/// use haru_cmaes::optimize::{optimize, OptimizeOptions};
+///
+/// let objective = Rastrigin { obj_dim: 5, dir: MinOrMax::Min };
+/// let options = OptimizeOptions::new()
+///     .set_popsize(12)
+///     .and_then(|o| o.set_tol(0.0))
+///     .and_then(|o| o.set_max_evals(1000))
+///     .unwrap();
+/// let result = optimize(objective, &options).unwrap();
+///
+/// // 83 full generations and one of 4 candidates
+/// assert_eq!(result.evaluations, 1000);
+/// assert_eq!(result.generations, 84);
+/// assert_eq!(result.remaining_evals, Some(0));
+/// assert_eq!(result.stop_reasons, vec![Termination::MaxEvaluations]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct OptimizationResult {
+    pub best_x: DVector<f32>,
+    pub best_f: f32,
+    pub mean: DVector<f32>,             // Final mean of the last run
+    pub sigma: f32,                     // Final step-size of the last run
+    pub evaluations: i32,               // Over all runs
+    pub generations: i32,               // Over all runs
+    pub stop_reasons: Vec<Termination>, // One per run
+    pub wall_time: Duration,
+    pub remaining_evals: Option<i32>,     // Left of `max_evals`
+    pub remaining_time: Option<Duration>, // Left of `max_time`
+    pub history: Option<Vec<Snapshot>>,   // Every generation, counted over all runs
+}
+
+/// Minimizes, or maximizes, `objective` with CMA-ES in one call.
+///
+/// Each run ends at `max_gens`, on convergence (`is_done`), when the search
+/// steps fall below `MIN_STEP` or when the budget is spent. Converged runs
+/// restart from `xstart` with twice the population, up to `restarts` times
+/// and while budget is left.
+///
+/// ```rust
+/// use haru_cmaes::fitness::MinOrMax;
+/// use haru_cmaes::objectives::Rosenbrock;
+/// use haru_cmaes::observer::Termination;
+/// use haru_cmaes::optimize::{optimize, OptimizeOptions};
+///
+/// fastrand::seed(5);
+/// let objective = Rosenbrock { obj_dim: 4, dir: MinOrMax::Min };
+/// let options = OptimizeOptions::new().set_history(true).unwrap();
+/// let result = optimize(objective, &options).unwrap();
+///
+/// assert!(result.best_f < 1e-6);
+/// assert!((result.best_x[0] - 1.0).abs() < 1e-2);
+/// assert_eq!(result.stop_reasons.len(), 1);
+/// assert_ne!(result.stop_reasons[0], Termination::MaxGenerations);
+/// assert_eq!(result.history.unwrap().len() as i32, result.generations);
+/// ```
+pub fn optimize(
+    objective: impl FitnessFunction,
+    options: &OptimizeOptions,
+) -> Result<OptimizationResult> {
+    optimize_observed(objective, options, &mut ())
+}
+
+/// `optimize`, calling the `observer` hooks in every run.
+///
+/// ```rust
+/// use haru_cmaes::fitness::MinOrMax;
+/// use haru_cmaes::objectives::SquareAndSum;
+/// use haru_cmaes::observer::Observer;
+/// use haru_cmaes::optimize::{optimize_observed, OptimizeOptions};
+/// use haru_cmaes::state::CmaesState;
+/// use anyhow::Result;
+///
+/// #[derive(Default)]
+/// struct Popsizes(Vec<usize>);
+///
+/// impl Observer for Popsizes {
+///     fn on_restart(&mut self, state: &CmaesState, _restart: usize) -> Result<()> {
+///         self.0.push(state.y.nrows());
+///         Ok(())
+///     }
+/// }
+///
+/// let objective = SquareAndSum { obj_dim: 3, dir: MinOrMax::Min };
+/// let options = OptimizeOptions::new()
+///     .set_popsize(6)
+///     .and_then(|o| o.set_restarts(2))
+///     .unwrap();
+/// let mut observer = Popsizes::default();
+/// let result = optimize_observed(objective, &options, &mut observer).unwrap();
+///
+/// assert_eq!(observer.0, vec![12, 24]);
+/// assert_eq!(result.stop_reasons.len(), 3);
+/// assert!(result.best_f < 1e-8);
+/// ```
+pub fn optimize_observed(
+    objective: impl FitnessFunction,
+    options: &OptimizeOptions,
+    observer: &mut impl Observer,
+) -> Result<OptimizationResult> {
+    let start = Instant::now();
+    let n = objective.cost_dim();
+    let xstart = options.xstart.clone().unwrap_or_else(|| vec![0.0; n]);
+    ensure!(
+        xstart.len() == n,
+        "Initial mean has {} coordinates, the objective {}",
+        xstart.len(),
+        n
+    );
+    let sign = match objective.optimization_type() {
+        MinOrMax::Min => 1.0,
+        MinOrMax::Max => -1.0,
+    };
+
+    let mut popsize = options.popsize.unwrap_or(4 + (3. * (n as f32).ln()) as i32);
+    let mut best_x = DVector::from_vec(xstart.clone());
+    let mut best_f = f32::MAX;
+    let (mut evaluations, mut generations) = (0, 0);
+    let mut stop_reasons = Vec::new();
+    let mut history = options.history.then(Vec::new);
+    let mut restart = 0;
+    let mut budget = Budget::new(options.max_evals, options.max_time);
+    loop {
+        let cmaes = CmaesAlgo::new(options.params(&xstart, popsize)?)?;
+        let mut state = CmaesState::init_state(&cmaes.params)?;
+        if restart > 0 {
+            observer.on_restart(&state, restart)?;
+        }
+
+        let mut reason = Termination::MaxGenerations;
+        while state.g < options.max_gens {
+            if let Some(spent) = budget.exhausted() {
+                reason = spent;
+                break;
+            }
+            let control;
+            (state, control) = cmaes.step_observed(state, &objective, observer, &mut budget)?;
+            if let Some(history) = history.as_mut() {
+                let mut snapshot = Snapshot::new(&state, sign);
+                snapshot.g += generations;
+                snapshot.evals += evaluations;
+                if best_f < state.best_y_fit[0] {
+                    snapshot.best_x.copy_from(&best_x);
+                    snapshot.best_f = sign * best_f;
+                }
+                history.push(snapshot);
+            }
+            if control == Control::Stop {
+                reason = Termination::Stopped;
+                break;
+            }
+            if cmaes.is_done(&state, state.g)? {
+                reason = Termination::Tolerance;
+                break;
+            }
+            if state.sigma * state.eig_vals.max().sqrt() < MIN_STEP {
+                reason = Termination::StepSize;
+                break;
+            }
+        }
+        observer.on_termination(&state, reason)?;
+
+        if state.best_y_fit[0] < best_f {
+            best_f = state.best_y_fit[0];
+            best_x.copy_from(&state.best_y);
+        }
+        evaluations += state.evals_count;
+        generations += state.g;
+        stop_reasons.push(reason);
+
+        let converged = matches!(reason, Termination::Tolerance | Termination::StepSize);
+        if !converged || restart == options.restarts || budget.exhausted().is_some() {
+            return Ok(OptimizationResult {
+                best_x,
+                best_f: sign * best_f,
+                mean: state.mean,
+                sigma: state.sigma,
+                evaluations,
+                generations,
+                stop_reasons,
+                wall_time: start.elapsed(),
+                remaining_evals: budget.evals,
+                remaining_time: budget.remaining_time(),
+                history,
+            });
+        }
+        restart += 1;
+        popsize *= 2;
+    }
+}
+
+/// Iterator over the generations of a run, yielding a `Snapshot` after each.
+///
+/// It owns the algorithm, state and objective, so runs compose with
+/// iterator adapters. Iteration ends once the search steps fall below
+/// `MIN_STEP`, when the budget of the parameters is spent, or on an error,
+/// which is then kept in `error`.
+///
+/// ```rust
+/// use haru_cmaes::fitness::MinOrMax;
+/// use haru_cmaes::objectives::SquareAndSum;
+/// use haru_cmaes::optimize::Generations;
+/// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+/// use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+/// use haru_cmaes::strategy::CmaesAlgo;
+///
+/// let params = CmaesParams::new().unwrap();
+/// let cmaes = CmaesAlgo::new(params).unwrap();
+/// let state = CmaesState::init_state(&cmaes.params).unwrap();
+/// let obj_func = SquareAndSum { obj_dim: 6, dir: MinOrMax::Min };
+/// let mut gens = Generations::new(cmaes, state, obj_func);
+///
+/// // Until the target, with at most 1000 generations
+/// let last = gens
+///     .by_ref()
+///     .take(1000)
+///     .take_while(|s| s.best_f > 1e-8)
+///     .inspect(|s| assert!(s.sigma > 0.0))
+///     .last()
+///     .unwrap();
+///
+/// assert!(last.g < 1000);
+/// assert!(gens.error.is_none());
+/// assert!(gens.state().unwrap().best_y_fit[0] <= 1e-8);
+/// ```
+pub struct Generations<F: FitnessFunction> {
+    pub cmaes: CmaesAlgo,
+    pub objective: F,
+    pub error: Option<Error>, // Error that ended the iteration
+    pub budget: Budget,       // Left of the parameters' budget, from `new`
+    state: Option<CmaesState>,
+    sign: f32,
+}
+
+impl<F: FitnessFunction> Generations<F> {
+    pub fn new(cmaes: CmaesAlgo, state: CmaesState, objective: F) -> Self {
+        let sign = match objective.optimization_type() {
+            MinOrMax::Min => 1.0,
+            MinOrMax::Max => -1.0,
+        };
+        let budget = Budget::from_params(&cmaes.params, &state);
+        Self {
+            cmaes,
+            objective,
+            error: None,
+            budget,
+            state: Some(state),
+            sign,
+        }
+    }
+
+    /// Current state, unless an error ended the iteration.
+    pub fn state(&self) -> Option<&CmaesState> {
+        self.state.as_ref()
+    }
+
+    /// Takes back the state, or the error that ended the iteration.
+    pub fn into_state(self) -> Result<CmaesState> {
+        match (self.state, self.error) {
+            (_, Some(error)) => Err(error),
+            (Some(state), None) => Ok(state),
+            (None, None) => unreachable!("state is only taken on error"),
+        }
+    }
+}
+
+impl<F: FitnessFunction> Iterator for Generations<F> {
+    type Item = Snapshot;
+
+    fn next(&mut self) -> Option<Snapshot> {
+        let state = self.state.take()?;
+        let stuck = state.sigma * state.eig_vals.max().sqrt() < MIN_STEP;
+        if stuck || self.budget.exhausted().is_some() {
+            self.state = Some(state);
+            return None;
+        }
+        match self
+            .cmaes
+            .step_observed(state, &self.objective, &mut (), &mut self.budget)
+        {
+            Ok((state, _)) => {
+                let snapshot = Snapshot::new(&state, self.sign);
+                self.state = Some(state);
+                Some(snapshot)
+            }
+            Err(error) => {
+                self.error = Some(error);
+                None
+            }
+        }
+    }
+}
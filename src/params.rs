@@ -1,25 +1,37 @@
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use nalgebra::DVector;
+use std::time::Duration;
 
 /// Parameters for CMA-ES (Covariance Matrix Adaptation Evolution Strategy).
 #[derive(Debug, Clone)]
 pub struct CmaesParams {
-    pub popsize: i32,          // Population size
-    pub xstart: Vec<f32>,      // Initial guess (mean vector)
-    pub num_gens: i32,         // Initially run for exact amount of generations
-    pub sigma: f32,            // Step-size (standard deviation)
-    pub tol: f32,              // Tolerance for convergence, optional
-    pub only_diag: bool,       // Whether to use only diag and no covariances or not
-    pub n: f32,                // Dimension of the problem space (xstart size)
-    pub mu: i32,               // Number of parents (best individuals)
-    pub weights: DVector<f32>, // Weights for recombination
-    pub mueff: f32,            // Effective number of parents
-    pub cc: f32,               // Cumulation constant for the rank-one update
-    pub cs: f32,               // Cumulation constant for the rank-mu update
-    pub c1: f32,               // Learning rate for the rank-one update
-    pub cmu: f32,              // Learning rate for the rank-mu update
-    pub damps: f32,            // Damping for step-size adaptation
-                               // pub lazy_gap_evals: f32, // Gap to postpone eigendecomposition
+    pub popsize: i32,                 // Population size
+    pub xstart: Vec<f32>,             // Initial guess (mean vector)
+    pub num_gens: i32,                // Initially run for exact amount of generations
+    pub sigma: f32,                   // Step-size (standard deviation)
+    pub tol: f32,                     // Tolerance for convergence, optional
+    pub only_diag: bool,              // Whether to use only diag and no covariances or not
+    pub n: f32,                       // Dimension of the problem space (xstart size)
+    pub mu: i32,                      // Number of parents (best individuals)
+    pub weights: DVector<f32>,        // Weights for recombination
+    pub mueff: f32,                   // Effective number of parents
+    pub cc: f32,                      // Cumulation constant for the rank-one update
+    pub cs: f32,                      // Cumulation constant for the rank-mu update
+    pub c1: f32,                      // Learning rate for the rank-one update
+    pub cmu: f32,                     // Learning rate for the rank-mu update
+    pub damps: f32,                   // Damping for step-size adaptation
+    pub step_size_rule: StepSizeRule, // Rule for step-size adaptation
+    pub max_evals: Option<i32>,       // Evaluation budget of the run drivers
+    pub max_time: Option<Duration>,   // Wall-clock budget of the run drivers
+                                      // pub lazy_gap_evals: f32, // Gap to postpone eigendecomposition
+}
+
+/// Step-size adaptation rules.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepSizeRule {
+    Csa,           // Cumulative step-size adaptation
+    Tpa,           // Two-point step-size adaptation
+    MedianSuccess, // Median success rule
 }
 
 /// Trait for CMA-ES parameters.
@@ -36,6 +48,9 @@ pub trait CmaesParamsValidator {
     fn set_tol(self, tol: f32) -> Result<Self::Validated>;
     fn set_only_diag(self, only_diag: bool) -> Result<Self::Validated>;
     fn set_num_gens(self, num_gens: i32) -> Result<Self::Validated>;
+    fn set_step_size_rule(self, rule: StepSizeRule) -> Result<Self::Validated>;
+    fn set_max_evals(self, max_evals: i32) -> Result<Self::Validated>;
+    fn set_max_time(self, max_time: Duration) -> Result<Self::Validated>;
 }
 
 /// Implmenting Trait for CMA-ES parameters.
@@ -61,6 +76,9 @@ impl CmaesParamsValidator for CmaesParams {
         let tol = 0.001;
         let only_diag = false;
         let num_gens = 100;
+        let step_size_rule = StepSizeRule::Csa;
+        let max_evals = None;
+        let max_time = None;
 
         let n = xstart.len() as f32;
         let mu = popsize / 2;
@@ -104,6 +122,9 @@ impl CmaesParamsValidator for CmaesParams {
             c1,
             cmu,
             damps,
+            step_size_rule,
+            max_evals,
+            max_time,
         };
         Ok(params)
     }
@@ -138,14 +159,25 @@ impl CmaesParamsValidator for CmaesParams {
     /// Sets population size.
     ///
     /// ```rust
-    /// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+    /// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator, StepSizeRule};
     ///
     /// let params = CmaesParams::new()
     ///     .and_then(|p| p.set_popsize(15));
     ///
     /// assert!(params.is_ok());
+    ///
+    /// // Two-point adaptation needs two candidates, whichever setter comes last
+    /// let params = CmaesParams::new()
+    ///     .and_then(|p| p.set_step_size_rule(StepSizeRule::Tpa))
+    ///     .and_then(|p| p.set_popsize(1));
+    ///
+    /// assert!(params.is_err());
     /// ```
     fn set_popsize(mut self, popsize: i32) -> Result<Self> {
+        ensure!(
+            self.step_size_rule != StepSizeRule::Tpa || popsize >= 2,
+            "Two-point adaptation requires a population of at least 2"
+        );
         self.popsize = popsize;
         self.update_dependent_params();
         Ok(self)
@@ -225,4 +257,56 @@ impl CmaesParamsValidator for CmaesParams {
         self.num_gens = num_gens;
         Ok(self)
     }
+
+    /// Sets step-size adaptation rule.
+    ///
+    /// ```rust
+    /// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator, StepSizeRule};
+    ///
+    /// let params = CmaesParams::new()
+    ///     .and_then(|p| p.set_step_size_rule(StepSizeRule::Tpa));
+    ///
+    /// assert!(params.is_ok());
+    /// ```
+    fn set_step_size_rule(mut self, rule: StepSizeRule) -> Result<Self::Validated> {
+        ensure!(
+            rule != StepSizeRule::Tpa || self.popsize >= 2,
+            "Two-point adaptation requires a population of at least 2"
+        );
+        self.step_size_rule = rule;
+        Ok(self)
+    }
+
+    /// Sets the evaluation budget, the last generation truncated to fit.
+    ///
+    /// ```rust
+    /// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+    ///
+    /// let params = CmaesParams::new()
+    ///     .and_then(|p| p.set_max_evals(1000));
+    ///
+    /// assert!(params.is_ok());
+    /// ```
+    fn set_max_evals(mut self, max_evals: i32) -> Result<Self::Validated> {
+        ensure!(max_evals >= 1, "Evaluation budget must be at least 1");
+        self.max_evals = Some(max_evals);
+        Ok(self)
+    }
+
+    /// Sets the wall-clock budget, checked between generations.
+    ///
+    /// ```rust
+    /// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+    /// use std::time::Duration;
+    ///
+    /// let params = CmaesParams::new()
+    ///     .and_then(|p| p.set_max_time(Duration::from_secs(60)));
+    ///
+    /// assert!(params.is_ok());
+    /// ```
+    fn set_max_time(mut self, max_time: Duration) -> Result<Self::Validated> {
+        ensure!(!max_time.is_zero(), "Time budget must be positive");
+        self.max_time = Some(max_time);
+        Ok(self)
+    }
 }
@@ -0,0 +1,414 @@
+use crate::fitness::Fitness;
+use crate::state::CmaesState;
+use anyhow::{ensure, Context, Result};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+const WIDTH: f64 = 1000.;
+const HEIGHT: f64 = 720.;
+const COLORS: [&str; 10] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f",
+    "#bcbd22", "#17becf",
+];
+
+/// State of one generation, as plotted.
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub g: i32,
+    pub evals: i32,
+    pub sigma: f32,
+    pub axis_ratio: f32, // Square root of the condition of the covariance
+    pub best: f32,       // Best fitness of the generation
+    pub median: f32,     // Median fitness of the generation
+    pub mean: Vec<f32>,
+    pub axes: Vec<f32>, // Principal axis lengths, sorted
+    pub stds: Vec<f32>, // Per-coordinate standard deviations, sigma * sqrt(diag(C))
+}
+
+/// Recorded run, to plot with `History::to_svg`.
+///
+/// ```rust
+/// use haru_cmaes::fitness::{FitnessEvaluator, MinOrMax};
+/// use haru_cmaes::objectives::Rosenbrock;
+/// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+/// use haru_cmaes::plot::History;
+/// use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+/// use haru_cmaes::strategy::{CmaesAlgo, CmaesAlgoOptimizer};
+///
+/// let params = CmaesParams::new().unwrap();
+/// let cmaes = CmaesAlgo::new(params).unwrap();
+/// let mut state = CmaesState::init_state(&cmaes.params).unwrap();
+/// let obj_func = Rosenbrock { obj_dim: 6, dir: MinOrMax::Min };
+/// let mut history = History::default();
+/// for _ in 0..100 {
+///     let mut pop = cmaes.ask(&mut state).unwrap();
+///     let mut fitness = obj_func.evaluate(&pop).unwrap();
+///     state = cmaes.tell(state, &mut pop, &mut fitness).unwrap();
+///     history.record(&state, &fitness);
+/// }
+///
+/// let svg = history.to_svg().unwrap();
+/// assert!(svg.starts_with("<svg"));
+/// assert_eq!(svg.matches("<polyline").count(), 4 + 6 + 6 + 6);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    pub records: Vec<Record>,
+}
+
+/// Numeric rows of a pycma data file, without the header.
+fn read_dat(dir: &Path, name: &str) -> Result<Vec<Vec<f32>>> {
+    let path = dir.join(format!("{}.dat", name));
+    let text =
+        fs::read_to_string(&path).with_context(|| format!("Cannot read {}", path.display()))?;
+    text.lines()
+        .filter(|l| !l.starts_with('%') && !l.trim().is_empty())
+        .map(|l| {
+            l.split_whitespace()
+                .map(|v| v.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .with_context(|| format!("Invalid line in {}: {}", path.display(), l))
+        })
+        .collect()
+}
+
+impl History {
+    /// Records the generation just told, from its sorted fitness.
+    pub fn record(&mut self, state: &CmaesState, fitness: &Fitness) {
+        let values = &fitness.values;
+        let lam = values.len();
+        let median = match lam {
+            0 => f32::NAN,
+            _ if lam % 2 == 1 => values[lam / 2],
+            _ => (values[lam / 2 - 1] + values[lam / 2]) / 2.,
+        };
+        let mut axes: Vec<f32> = state.eig_vals.iter().map(|e| e.max(0.).sqrt()).collect();
+        axes.sort_by(|a, b| a.total_cmp(b));
+        self.records.push(Record {
+            g: state.g,
+            evals: state.evals_count,
+            sigma: state.sigma,
+            axis_ratio: axes[axes.len() - 1] / axes[0],
+            best: values.get(0).copied().unwrap_or(f32::NAN),
+            median,
+            mean: state.mean.iter().copied().collect(),
+            axes,
+            stds: state
+                .cov
+                .diagonal()
+                .iter()
+                .map(|c| state.sigma * c.sqrt())
+                .collect(),
+        });
+    }
+
+    /// Loads a run from the files of a `CmaDataLogger`.
+    ///
+    /// ```rust
+    /// use haru_cmaes::datalogger::CmaDataLogger;
+    /// use haru_cmaes::fitness::{FitnessEvaluator, MinOrMax};
+    /// use haru_cmaes::objectives::SquareAndSum;
+    /// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+    /// use haru_cmaes::plot::History;
+    /// use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+    /// use haru_cmaes::strategy::{CmaesAlgo, CmaesAlgoOptimizer};
+    ///
+    /// let dir = std::env::temp_dir().join("haru_cmaes_plot_doc");
+    /// let mut logger = CmaDataLogger::new(&dir, 1).unwrap();
+    /// let params = CmaesParams::new().unwrap();
+    /// let cmaes = CmaesAlgo::new(params).unwrap();
+    /// let mut state = CmaesState::init_state(&cmaes.params).unwrap();
+    /// let obj_func = SquareAndSum { obj_dim: 6, dir: MinOrMax::Min };
+    /// for _ in 0..30 {
+    ///     let mut pop = cmaes.ask(&mut state).unwrap();
+    ///     let mut fitness = obj_func.evaluate(&pop).unwrap();
+    ///     state = cmaes.tell(state, &mut pop, &mut fitness).unwrap();
+    ///     logger.add(&state, &pop, &fitness).unwrap();
+    /// }
+    ///
+    /// let history = History::from_outcmaes(&dir).unwrap();
+    /// assert_eq!(history.records.len(), 30);
+    /// assert_eq!(history.records[29].mean.len(), 6);
+    /// history.write_svg(dir.join("run.svg")).unwrap();
+    /// ```
+    pub fn from_outcmaes(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let (fit, xmean) = (read_dat(dir, "fit")?, read_dat(dir, "xmean")?);
+        let (stddev, axlen) = (read_dat(dir, "stddev")?, read_dat(dir, "axlen")?);
+        let n = fit.len();
+        ensure!(
+            xmean.len() == n && stddev.len() == n && axlen.len() == n,
+            "Data files in {} have different numbers of lines",
+            dir.display()
+        );
+        let records = (0..n)
+            .map(|i| {
+                ensure!(
+                    fit[i].len() >= 8 && xmean[i].len() > 5,
+                    "Line {} is too short",
+                    i + 1
+                );
+                Ok(Record {
+                    g: fit[i][0] as i32,
+                    evals: fit[i][1] as i32,
+                    sigma: fit[i][2],
+                    axis_ratio: fit[i][3],
+                    best: fit[i][5],
+                    median: fit[i][6],
+                    mean: xmean[i][5..].to_vec(),
+                    axes: axlen[i].get(5..).unwrap_or_default().to_vec(),
+                    stds: stddev[i].get(5..).unwrap_or_default().to_vec(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { records })
+    }
+
+    /// Renders the four-panel diagnostic figure.
+    ///
+    /// Against evaluations: best and median fitness, sigma and axis ratio;
+    /// mean coordinates; principal axis lengths; coordinate deviations.
+    /// Fitness is plotted in absolute value on a log scale.
+    pub fn to_svg(&self) -> Result<String> {
+        ensure!(!self.records.is_empty(), "History is empty");
+        let x: Vec<f64> = self.records.iter().map(|r| r.evals as f64).collect();
+        let series = |f: &dyn Fn(&Record) -> f32| -> Vec<f64> {
+            self.records.iter().map(|r| f(r) as f64).collect()
+        };
+        let coords = |f: &dyn Fn(&Record) -> &Vec<f32>, prefix: &str| {
+            let n = f(&self.records[0]).len();
+            (0..n)
+                .map(|i| {
+                    let label = format!("{}{}", prefix, i + 1);
+                    (label, series(&|r| f(r).get(i).copied().unwrap_or(f32::NAN)))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let panels = [
+            Panel {
+                title: "abs(f), sigma and axis ratio",
+                log: true,
+                series: vec![
+                    ("best |f|".to_string(), series(&|r| r.best.abs())),
+                    ("median |f|".to_string(), series(&|r| r.median.abs())),
+                    ("sigma".to_string(), series(&|r| r.sigma)),
+                    ("axis ratio".to_string(), series(&|r| r.axis_ratio)),
+                ],
+                legend: true,
+            },
+            Panel {
+                title: "Object variables (mean)",
+                log: false,
+                series: coords(&|r| &r.mean, "x"),
+                legend: false,
+            },
+            Panel {
+                title: "Principal axes lengths",
+                log: true,
+                series: coords(&|r| &r.axes, "a"),
+                legend: false,
+            },
+            Panel {
+                title: "Standard deviations in all coordinates",
+                log: true,
+                series: coords(&|r| &r.stds, "x"),
+                legend: false,
+            },
+        ];
+
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"sans-serif\" font-size=\"11\">",
+            WIDTH, HEIGHT
+        )?;
+        writeln!(
+            svg,
+            "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>",
+            WIDTH, HEIGHT
+        )?;
+        for (k, panel) in panels.iter().enumerate() {
+            let left = 70. + (k % 2) as f64 * WIDTH / 2.;
+            let top = 40. + (k / 2) as f64 * HEIGHT / 2.;
+            panel.draw(
+                &mut svg,
+                &x,
+                left,
+                top,
+                WIDTH / 2. - 120.,
+                HEIGHT / 2. - 90.,
+            )?;
+        }
+        writeln!(svg, "</svg>")?;
+        Ok(svg)
+    }
+
+    /// Writes the diagnostic figure to an SVG file.
+    pub fn write_svg(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_svg()?).with_context(|| format!("Cannot write {}", path.display()))
+    }
+}
+
+/// One subplot, with its series against the shared evaluations axis.
+struct Panel {
+    title: &'static str,
+    log: bool,
+    series: Vec<(String, Vec<f64>)>,
+    legend: bool,
+}
+
+/// Formats a tick label compactly.
+fn tick_label(v: f64, log: bool) -> String {
+    if log {
+        format!("1e{}", v)
+    } else if v != 0. && (v.abs() >= 1e4 || v.abs() < 1e-2) {
+        format!("{:.1e}", v)
+    } else {
+        let s = format!("{:.3}", v);
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+impl Panel {
+    fn draw(
+        &self,
+        svg: &mut String,
+        x: &[f64],
+        left: f64,
+        top: f64,
+        width: f64,
+        height: f64,
+    ) -> Result<()> {
+        let transform = |v: f64| {
+            if self.log {
+                (v > 0.).then(|| v.log10())
+            } else {
+                v.is_finite().then_some(v)
+            }
+        };
+        let values: Vec<f64> = self
+            .series
+            .iter()
+            .flat_map(|(_, s)| s.iter().filter_map(|&v| transform(v)))
+            .filter(|v| v.is_finite())
+            .collect();
+        let (mut y_min, mut y_max) = values
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| {
+                (lo.min(v), hi.max(v))
+            });
+        if values.is_empty() {
+            (y_min, y_max) = (0., 1.);
+        }
+        if self.log {
+            (y_min, y_max) = (y_min.floor(), y_max.ceil());
+        }
+        if y_max - y_min < 1e-12 {
+            (y_min, y_max) = (y_min - 1., y_max + 1.);
+        }
+        let (x_min, x_max) = (x[0].min(0.), x[x.len() - 1].max(x[0] + 1.));
+        let px = |v: f64| left + (v - x_min) / (x_max - x_min) * width;
+        let py = |v: f64| top + height - (v - y_min) / (y_max - y_min) * height;
+
+        // Frame, title and ticks
+        writeln!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"none\" stroke=\"black\"/>",
+            left, top, width, height
+        )?;
+        writeln!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" font-size=\"13\">{}</text>",
+            left + width / 2.,
+            top - 10.,
+            self.title
+        )?;
+        let y_ticks: Vec<f64> = if self.log {
+            let step = ((y_max - y_min) / 6.).ceil().max(1.);
+            (0..)
+                .map(|i| y_min + i as f64 * step)
+                .take_while(|&v| v <= y_max)
+                .collect()
+        } else {
+            (0..=4)
+                .map(|i| y_min + i as f64 * (y_max - y_min) / 4.)
+                .collect()
+        };
+        for v in y_ticks {
+            let y = py(v);
+            writeln!(
+                svg,
+                "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#dddddd\"/>",
+                left,
+                y,
+                left + width,
+                y
+            )?;
+            writeln!(
+                svg,
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>",
+                left - 5.,
+                y + 4.,
+                tick_label(v, self.log)
+            )?;
+        }
+        for i in 0..=4 {
+            let v = x_min + i as f64 * (x_max - x_min) / 4.;
+            writeln!(
+                svg,
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{:.0}</text>",
+                px(v),
+                top + height + 15.,
+                v
+            )?;
+        }
+        writeln!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">evaluations</text>",
+            left + width / 2.,
+            top + height + 30.
+        )?;
+
+        // Series, labelled at their last point or in a legend
+        for (k, (label, series)) in self.series.iter().enumerate() {
+            let color = COLORS[k % COLORS.len()];
+            let points: Vec<String> = x
+                .iter()
+                .zip(series)
+                .filter_map(|(&xv, &yv)| {
+                    transform(yv).map(|t| format!("{:.1},{:.1}", px(xv), py(t)))
+                })
+                .collect();
+            writeln!(
+                svg,
+                "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"1.2\" points=\"{}\"/>",
+                color,
+                points.join(" ")
+            )?;
+            if self.legend {
+                let y = top + 12. + 14. * k as f64;
+                writeln!(
+                    svg,
+                    "<text x=\"{:.1}\" y=\"{:.1}\" fill=\"{}\">{}</text>",
+                    left + width - 80.,
+                    y,
+                    color,
+                    label
+                )?;
+            } else if let Some(last) = series.iter().rev().find_map(|&v| transform(v)) {
+                writeln!(
+                    svg,
+                    "<text x=\"{:.1}\" y=\"{:.1}\" fill=\"{}\">{}</text>",
+                    left + width + 4.,
+                    py(last) + 4.,
+                    color,
+                    label
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
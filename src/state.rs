@@ -22,10 +22,11 @@ pub struct CmaesState {
     pub evals_count: i32,       // Number of evaluations performed.
     pub ps: DVector<f32>,       // Evolution path for step-size adaptation.
     pub pc: DVector<f32>,       // Evolution path for covariance matrix adaptation.
-                                ////////////////
-                                // TODO
-                                // Allow flag for verbose state, maybe with tracing
-                                ////////////////
+    pub prev_mean: DVector<f32>, // Mean of the previous generation.
+    pub prev_fit: DVector<f32>, // Sorted fitness values of the previous generation.
+    pub step_size_s: f32,       // Accumulator for TPA and median success rule.
+    pub injected: Vec<(DVector<f32>, DVector<f32>)>, // Injected solutions and their clipped candidates, until tell.
+    pub tpa_pair: bool, // Whether rows 0 and 1 hold the TPA pair placed by ask, until tell.
 }
 
 /// Trait for CMA-ES State
@@ -70,13 +71,16 @@ impl CmaesStateLogic for CmaesState {
         let cov: DMatrix<f32> = DMatrix::identity(params.xstart.len(), params.xstart.len());
         let inv_sqrt: DMatrix<f32> = DMatrix::identity(params.xstart.len(), params.xstart.len());
         let eig_vecs: DMatrix<f32> = DMatrix::identity(params.xstart.len(), params.xstart.len());
-        let eig_vals: DVector<f32> = DVector::identity(params.xstart.len());
+        let eig_vals: DVector<f32> = DVector::from_element(params.xstart.len(), 1.0);
         let mean: DVector<f32> = DVector::from_vec(params.xstart.clone());
         let sigma: f32 = params.sigma;
         let g: i32 = 0;
         let evals_count = 0;
         let ps: DVector<f32> = DVector::zeros(params.xstart.len());
         let pc: DVector<f32> = DVector::zeros(params.xstart.len());
+        let prev_mean: DVector<f32> = mean.clone();
+        let prev_fit: DVector<f32> = DVector::zeros(0);
+        let step_size_s: f32 = 0.0;
 
         Ok(CmaesState {
             normal_distr,
@@ -95,6 +99,11 @@ impl CmaesStateLogic for CmaesState {
             evals_count,
             ps,
             pc,
+            prev_mean,
+            prev_fit,
+            step_size_s,
+            injected: Vec::new(),
+            tpa_pair: false,
         })
     }
 
@@ -105,6 +114,10 @@ impl CmaesStateLogic for CmaesState {
     }
 
     /// Performs eigen decomposition on the covariance matrix.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "eigen_decomposition", level = "trace", skip_all, fields(g = self.g))
+    )]
     fn eigen_decomposition(&mut self, params: &CmaesParams) -> Result<()> {
         // Ensure symmetric covariance
         self.cov = (&self.cov + &self.cov.transpose()) / 2.0;
@@ -128,13 +141,6 @@ impl CmaesStateLogic for CmaesState {
             }
         }
 
-        // println!("cov {:?}", &self.cov);
-        ////////////////
-        // dbg!(&self);
-        // dbg!(&self);
-        // println!("");
-        ////////////////
-
         // Perform eigen decomposition: C = B * Λ * B^T
         #[cfg(any(
             feature = "openblas",
@@ -150,7 +156,7 @@ impl CmaesStateLogic for CmaesState {
             feature = "accelerate",
             feature = "intel-mkl"
         )))]
-        let eigen = SymmetricEigen::try_new(self.cov.clone(), 1e-20, 0).unwrap();
+        let eigen = SymmetricEigen::try_new(self.cov.clone(), f32::EPSILON, 0).unwrap();
         let mut eig_vals: DVector<f32> = eigen.eigenvalues;
         let eig_vecs: DMatrix<f32> = eigen.eigenvectors;
 
@@ -158,8 +164,6 @@ impl CmaesStateLogic for CmaesState {
         eig_vals.iter_mut().for_each(|val| {
             if *val < 0.0 {
                 *val = 0.1; // Adjust negative valenvalues
-            } else if *val > 10.0 {
-                *val = 10.0; // Clamp to a maximum value
             }
         });
 
@@ -176,11 +180,13 @@ impl CmaesStateLogic for CmaesState {
         let inv_sqrt_diag = DMatrix::from_diagonal(&eig_vals.map(|eig| eig.powf(-0.5)));
         self.inv_sqrt = &eig_vecs * &inv_sqrt_diag * eig_vecs.transpose();
 
-        // print!("{:?} ", &self.cov.mean());
-        // print!("{:?} ", &eig_vals.mean());
-        // print!("{:?} ", &eig_vecs.mean());
-        // println!("{:?}", &inv_sqrt_diag.mean());
-        // println!("{}", &eig_vecs);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            condition = eig_vals.max() / eig_vals.min(),
+            min_eig = eig_vals.min(),
+            max_eig = eig_vals.max(),
+            "decomposed"
+        );
 
         // Store
         self.eig_vecs = eig_vecs;
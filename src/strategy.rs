@@ -1,12 +1,17 @@
 use crate::fitness::{FitnessEvaluator, FitnessFunction, PopulationY, PopulationZ};
+use crate::metrics::{Phase, PhaseTimer};
+use crate::observer::{Control, Observer, Termination};
+use crate::utils::standard_normal;
 // use crate::utils::median;
 use crate::{
     fitness::Fitness,
-    params::CmaesParams,
+    params::{CmaesParams, StepSizeRule},
     state::{CmaesState, CmaesStateLogic},
 };
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use nalgebra::{DMatrix, DVector};
+use std::borrow::Cow;
+use std::time::{Duration, Instant};
 
 /// Struct to hold the algorithm's data and ask and tell methods
 #[derive(Debug)]
@@ -31,6 +36,10 @@ impl CmaesAlgo {
     ///
     pub fn new(params: CmaesParams) -> Result<Self> {
         // let params = CmaesParams::validate(params)?;
+        ensure!(
+            params.step_size_rule != StepSizeRule::Tpa || params.popsize >= 2,
+            "Two-point adaptation requires a population of at least 2"
+        );
         Ok(Self { params })
     }
 
@@ -49,26 +58,252 @@ impl CmaesAlgo {
     /// assert!(z.is_ok());
     /// ```
     pub fn ask_z(&self, state: &mut CmaesState) -> Result<PopulationZ> {
-        let data: Vec<f32> = (0..self.params.popsize)
-            .flat_map(|_i| {
-                (0..self.params.xstart.len()).map(|_| {
-                    // Convert uniform random numbers to standard normal distribution
-                    let u1 = fastrand::f32();
-                    let u2 = fastrand::f32();
-                    (-2.0 * u1.clamp(0.0001, 0.9999).ln()).sqrt()
-                        * (2.0 * std::f32::consts::PI * u2).cos()
-                })
-            })
-            .collect();
-        let z = DMatrix::from_row_slice(
-            self.params.popsize as usize,
-            self.params.xstart.len(),
-            &data,
-        );
+        let z = standard_normal(self.params.popsize as usize, self.params.xstart.len());
         state.z.copy_from(&z);
         // state.z = z.clone();
         Ok(PopulationZ { z })
     }
+
+    /// Maps standard normal samples to candidates, `y = mean + sigma * B * sqrt(D) * z`.
+    ///
+    /// Uses the eigen decomposition of the state as left by the last `ask`.
+    ///
+    /// ```rust
+    /// use haru_cmaes::fitness::PopulationZ;
+    /// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+    /// use haru_cmaes::strategy::CmaesAlgo;
+    /// use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+    /// use nalgebra::DMatrix;
+    ///
+    /// let params = CmaesParams::new()
+    ///     .and_then(|p| p.set_xstart(2, 1.0))
+    ///     .and_then(|p| p.set_sigma(0.5))
+    ///     .unwrap();
+    /// let cmaes = CmaesAlgo::new(params).unwrap();
+    /// let state = CmaesState::init_state(&cmaes.params).unwrap();
+    /// let z = PopulationZ { z: DMatrix::from_row_slice(1, 2, &[2.0, -2.0]) };
+    /// let y = cmaes.scale_z(&state, &z);
+    ///
+    /// assert_eq!(y.y, DMatrix::from_row_slice(1, 2, &[2.0, 0.0]));
+    /// ```
+    pub fn scale_z(&self, state: &CmaesState, pop: &PopulationZ) -> PopulationY {
+        let eig_vals_sqrt: DMatrix<f32> = DMatrix::from_diagonal(&state.eig_vals.map(|x| x.sqrt()));
+        let scaled_z: DMatrix<f32> = pop.z.map(|x| x * state.sigma) * &eig_vals_sqrt;
+        let rotated_z: DMatrix<f32> = scaled_z * &state.eig_vecs.transpose();
+        let y: DMatrix<f32> = DMatrix::from_rows(
+            &rotated_z
+                .row_iter()
+                .map(|row| row + &state.mean.transpose())
+                .collect::<Vec<_>>(),
+        );
+        PopulationY { y }
+    }
+
+    /// Injects external solutions into the first rows of an ask'd population.
+    ///
+    /// The solutions are evaluated as given. Following Hansen (2011), `tell`
+    /// clips their steps `x - mean` to a Mahalanobis length of at most
+    /// `sqrt(n) + 2n / (n + 2)` under the current distribution, so the
+    /// covariance update stays valid. The clipped candidates are recorded in
+    /// `state.y`, `state.z` and `state.injected` until then. Solutions
+    /// replace the pair placed by TPA, whose update `tell` then skips.
+    ///
+    /// ```rust
+    /// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+    /// use haru_cmaes::strategy::{CmaesAlgo, CmaesAlgoOptimizer};
+    /// use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+    /// use haru_cmaes::fitness::{FitnessEvaluator, MinOrMax};
+    /// use haru_cmaes::objectives::SquareAndSum;
+    /// use nalgebra::DVector;
+    ///
+    /// let params = CmaesParams::new()
+    ///     .and_then(|p| p.set_xstart(6, 100.0))
+    ///     .unwrap();
+    /// let cmaes = CmaesAlgo::new(params).unwrap();
+    /// let mut state = CmaesState::init_state(&cmaes.params).unwrap();
+    /// let mut y = cmaes.ask(&mut state).unwrap();
+    ///
+    /// // Both solutions are evaluated as given, only the far step is clipped
+    /// let near = DVector::from_element(6, 100.1);
+    /// let far = DVector::zeros(6);
+    /// cmaes.inject(&mut state, &mut y, &[near.clone(), far.clone()]).unwrap();
+    /// assert_eq!(y.y.row(1).transpose(), far);
+    /// assert_eq!(state.y.row(0).transpose(), near);
+    /// assert!(state.y.row(1).min() > 0.0);
+    ///
+    /// let obj_func = SquareAndSum { obj_dim: 6, dir: MinOrMax::Min };
+    /// let mut fitness = obj_func.evaluate(&y).unwrap();
+    /// assert_eq!(fitness.values[1], 0.0);
+    ///
+    /// // The far solution is the best one, but moves the mean by its clipped step
+    /// let state = cmaes.tell(state, &mut y, &mut fitness).unwrap();
+    /// assert_eq!(state.best_y, far);
+    /// assert!(state.mean.min() > 90.0);
+    /// assert!(state.injected.is_empty());
+    /// ```
+    ///
+    /// With TPA, injecting after `ask` leaves the step size to the next pair:
+    ///
+    /// ```rust
+    /// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator, StepSizeRule};
+    /// use haru_cmaes::strategy::{CmaesAlgo, CmaesAlgoOptimizer};
+    /// use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+    /// use haru_cmaes::fitness::{FitnessEvaluator, MinOrMax};
+    /// use haru_cmaes::objectives::SquareAndSum;
+    /// use nalgebra::DVector;
+    ///
+    /// let params = CmaesParams::new()
+    ///     .and_then(|p| p.set_xstart(4, 1.0))
+    ///     .and_then(|p| p.set_step_size_rule(StepSizeRule::Tpa))
+    ///     .unwrap();
+    /// let cmaes = CmaesAlgo::new(params).unwrap();
+    /// let mut state = CmaesState::init_state(&cmaes.params).unwrap();
+    /// let obj_func = SquareAndSum { obj_dim: 4, dir: MinOrMax::Min };
+    /// for _ in 0..3 {
+    ///     let mut y = cmaes.ask(&mut state).unwrap();
+    ///     let mut fitness = obj_func.evaluate(&y).unwrap();
+    ///     state = cmaes.tell(state, &mut y, &mut fitness).unwrap();
+    /// }
+    ///
+    /// let mut y = cmaes.ask(&mut state).unwrap();
+    /// assert!(state.tpa_pair);
+    /// cmaes.inject(&mut state, &mut y, &[DVector::zeros(4)]).unwrap();
+    /// assert!(!state.tpa_pair);
+    ///
+    /// let (sigma, s) = (state.sigma, state.step_size_s);
+    /// let mut fitness = obj_func.evaluate(&y).unwrap();
+    /// let state = cmaes.tell(state, &mut y, &mut fitness).unwrap();
+    /// assert_eq!((state.sigma, state.step_size_s), (sigma, s));
+    /// ```
+    pub fn inject(
+        &self,
+        state: &mut CmaesState,
+        pop: &mut PopulationY,
+        solutions: &[DVector<f32>],
+    ) -> Result<()> {
+        ensure!(
+            solutions.len() <= pop.y.nrows(),
+            "Cannot inject more solutions than the population size"
+        );
+        let n = state.mean.len() as f32;
+        let max_len = n.sqrt() + 2. * n / (n + 2.);
+        if !solutions.is_empty() {
+            state.tpa_pair = false;
+        }
+        for (i, x) in solutions.iter().enumerate() {
+            ensure!(
+                x.len() == state.mean.len(),
+                "Injected solution has wrong dimension"
+            );
+            let dx: DVector<f32> = x - &state.mean;
+            let mahalanobis = (&state.inv_sqrt * &dx).norm() / state.sigma;
+            let dx = dx * f32::min(1., max_len / mahalanobis);
+            let z: DVector<f32> = (state.eig_vecs.transpose() * &dx)
+                .component_div(&state.eig_vals.map(|v| v.sqrt()))
+                / state.sigma;
+            let clipped = &state.mean + dx;
+            pop.y.set_row(i, &x.transpose());
+            state.y.set_row(i, &clipped.transpose());
+            state.z.set_row(i, &z.transpose());
+            state.injected.push((x.clone(), clipped));
+        }
+        Ok(())
+    }
+}
+
+/// Evaluations and time left to a run.
+///
+/// Drivers check it between generations and truncate the last population
+/// to the evaluations left.
+///
+/// ```rust
+/// use haru_cmaes::observer::Termination;
+/// use haru_cmaes::strategy::Budget;
+///
+/// let mut budget = Budget::new(Some(25), None);
+/// assert_eq!(budget.candidates(10), 10);
+/// budget.spend(20);
+/// assert_eq!(budget.candidates(10), 5);
+/// budget.spend(5);
+/// assert_eq!(budget.exhausted(), Some(Termination::MaxEvaluations));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Budget {
+    pub evals: Option<i32>,        // Evaluations left
+    pub deadline: Option<Instant>, // End of the time budget
+}
+
+impl Budget {
+    /// Starts the clock on `max_time`.
+    pub fn new(max_evals: Option<i32>, max_time: Option<Duration>) -> Self {
+        Self {
+            evals: max_evals,
+            deadline: max_time.map(|t| Instant::now() + t),
+        }
+    }
+
+    /// Budget set in `params`, less the evaluations `state` already spent,
+    /// starting now.
+    ///
+    /// ```rust
+    /// use haru_cmaes::fitness::MinOrMax;
+    /// use haru_cmaes::objectives::SquareAndSum;
+    /// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+    /// use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+    /// use haru_cmaes::strategy::{Budget, CmaesAlgo, CmaesAlgoOptimizer};
+    ///
+    /// let params = CmaesParams::new()
+    ///     .and_then(|p| p.set_xstart(2, 1.0))
+    ///     .and_then(|p| p.set_max_evals(100))
+    ///     .unwrap();
+    /// let cmaes = CmaesAlgo::new(params).unwrap();
+    /// let mut state = CmaesState::init_state(&cmaes.params).unwrap();
+    /// state.evals_count = 60;
+    /// assert_eq!(Budget::from_params(&cmaes.params, &state).evals, Some(40));
+    ///
+    /// // A resumed run spends only what is left
+    /// let obj_func = SquareAndSum { obj_dim: 2, dir: MinOrMax::Min };
+    /// let state = cmaes.rollout_fold(state, obj_func).unwrap();
+    /// assert_eq!(state.evals_count, 100);
+    /// ```
+    pub fn from_params(params: &CmaesParams, state: &CmaesState) -> Self {
+        Self::new(
+            params.max_evals.map(|m| m - state.evals_count),
+            params.max_time,
+        )
+    }
+
+    /// Which limit is reached, if any.
+    pub fn exhausted(&self) -> Option<Termination> {
+        if self.evals.is_some_and(|e| e <= 0) {
+            Some(Termination::MaxEvaluations)
+        } else if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            Some(Termination::MaxTime)
+        } else {
+            None
+        }
+    }
+
+    /// How many of `popsize` candidates fit in the evaluations left.
+    pub fn candidates(&self, popsize: usize) -> usize {
+        match self.evals {
+            Some(e) => popsize.min(e.max(0) as usize),
+            None => popsize,
+        }
+    }
+
+    /// Counts `evals` evaluations against the budget.
+    pub fn spend(&mut self, evals: usize) {
+        if let Some(e) = self.evals.as_mut() {
+            *e -= evals as i32;
+        }
+    }
+
+    /// Time left, if limited.
+    pub fn remaining_time(&self) -> Option<Duration> {
+        self.deadline
+            .map(|d| d.saturating_duration_since(Instant::now()))
+    }
 }
 
 /// Trait for CMA-ES algorithm.
@@ -77,6 +312,7 @@ pub trait CmaesAlgoOptimizer {
     type NewState;
     type Done;
 
+    fn params(&self) -> &CmaesParams;
     fn ask(&self, state: &mut CmaesState) -> Result<Self::NewPopulation>;
     fn tell(
         &self,
@@ -90,6 +326,103 @@ pub trait CmaesAlgoOptimizer {
         state: CmaesState,
         objective_function: impl FitnessFunction,
     ) -> Result<Self::NewState>;
+
+    /// `tell`, or for a population truncated by the evaluation budget, only
+    /// counting its evaluations and recording its best candidate, as too few
+    /// were evaluated to update the distribution.
+    fn tell_truncated(
+        &self,
+        mut state: CmaesState,
+        pop: &mut PopulationY,
+        fitness: &mut Fitness,
+    ) -> Result<CmaesState>
+    where
+        Self: Sized + CmaesAlgoOptimizer<NewState = CmaesState>,
+    {
+        if fitness.values.len() >= self.params().popsize as usize {
+            return self.tell(state, pop, fitness);
+        }
+        state.g += 1;
+        state.evals_count += fitness.values.nrows() as i32;
+        rank_population(&mut state, pop, fitness);
+        Ok(state)
+    }
+
+    /// Runs one generation within `budget`, calling the `observer` hooks.
+    ///
+    /// Returns the new state, told only if no hook before `tell` stopped,
+    /// and whether a hook asked to stop.
+    fn step_observed(
+        &self,
+        mut state: CmaesState,
+        objective_function: &impl FitnessFunction,
+        observer: &mut impl Observer,
+        budget: &mut Budget,
+    ) -> Result<(CmaesState, Control)>
+    where
+        Self: Sized + CmaesAlgoOptimizer<NewPopulation = PopulationY, NewState = CmaesState>,
+    {
+        let mut pop = self.ask(&mut state)?;
+        let lam = budget.candidates(pop.y.nrows());
+        if lam < pop.y.nrows() {
+            pop.y = pop.y.rows(0, lam).into_owned();
+        }
+        if observer.after_ask(&state, &pop)? == Control::Stop {
+            return Ok((state, Control::Stop));
+        }
+        let mut fitness = objective_function.evaluate(&pop)?;
+        budget.spend(lam);
+        if observer.after_evaluate(&state, &pop, &fitness)? == Control::Stop {
+            return Ok((state, Control::Stop));
+        }
+        let state = self.tell_truncated(state, &mut pop, &mut fitness)?;
+        let control = observer.after_tell(&state, &pop, &fitness)?;
+        Ok((state, control))
+    }
+
+    /// Runs up to `num_gens` generations, calling the `observer` hooks.
+    ///
+    /// The run ends early when a hook returns `Control::Stop`, or when the
+    /// budget of `params` is spent, counting the evaluations `state` already
+    /// made.
+    fn rollout_observed(
+        &self,
+        mut state: CmaesState,
+        objective_function: impl FitnessFunction,
+        num_gens: i32,
+        observer: &mut impl Observer,
+    ) -> Result<CmaesState>
+    where
+        Self: Sized + CmaesAlgoOptimizer<NewPopulation = PopulationY, NewState = CmaesState>,
+    {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("rollout", num_gens).entered();
+        let mut reason = Termination::MaxGenerations;
+        let mut budget = Budget::from_params(self.params(), &state);
+        for _ in 0..num_gens {
+            if let Some(spent) = budget.exhausted() {
+                reason = spent;
+                break;
+            }
+            let control;
+            (state, control) =
+                self.step_observed(state, &objective_function, observer, &mut budget)?;
+            if control == Control::Stop {
+                reason = Termination::Stopped;
+                break;
+            }
+        }
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            ?reason,
+            g = state.g,
+            evals = state.evals_count,
+            best_fitness = state.best_y_fit[0],
+            "terminated"
+        );
+        observer.on_termination(&state, reason)?;
+        Ok(state)
+    }
 }
 
 /// Implementing Trait for CMA-ES algorithm.
@@ -98,6 +431,10 @@ impl CmaesAlgoOptimizer for CmaesAlgo {
     type NewState = CmaesState;
     type Done = bool;
 
+    fn params(&self) -> &CmaesParams {
+        &self.params
+    }
+
     /// ASK
     /// Generates a new population.
     ///
@@ -113,72 +450,38 @@ impl CmaesAlgoOptimizer for CmaesAlgo {
     ///
     /// assert!(y.is_ok());
     /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "ask", level = "debug", skip_all, fields(g = state.g))
+    )]
     fn ask(&self, state: &mut CmaesState) -> Result<Self::NewPopulation> {
-        #[cfg(feature = "profile_memory")]
         {
-            use anyhow::Result;
-            use std::{fs::File, io::Read};
-
-            fn get_memory_usage() -> Result<usize> {
-                let mut s = String::new();
-                File::open("/proc/self/statm")?.read_to_string(&mut s)?;
-                let fields: Vec<&str> = s.split_whitespace().collect();
-                Ok(fields[1].parse::<usize>().unwrap() * 4096 / 1000000) // Resident Set Size in bytes
-            }
-
-            fn format_number(num: usize) -> String {
-                let num_str = num.to_string();
-                let result: String = num_str
-                    .chars()
-                    .rev()
-                    .enumerate()
-                    .map(|(i, c)| {
-                        if i == 3 {
-                            return ',';
-                        }
-                        c
-                    })
-                    .collect();
-                result.chars().rev().collect()
+            let _timer = PhaseTimer::start(Phase::Eigendecomposition);
+            state.prepare_ask(&self.params)?;
+        }
+        let _timer = PhaseTimer::start(Phase::Sampling);
+
+        let z: PopulationZ = self.ask_z(state)?;
+        let mut pop = self.scale_z(state, &z);
+        state.y.copy_from(&pop.y);
+        state.injected.clear();
+        state.tpa_pair = false;
+
+        // TPA: first two candidates go along +/- the last mean shift
+        if self.params.step_size_rule == StepSizeRule::Tpa && state.g > 0 {
+            let dm: DVector<f32> = &state.mean - &state.prev_mean;
+            let mahalanobis = (&state.inv_sqrt * &dm).norm() / state.sigma;
+            if mahalanobis > 0.0 {
+                let step = dm * (z.z.row(0).norm() / mahalanobis);
+                let pair = [&state.mean + &step, &state.mean - &step];
+                self.inject(state, &mut pop, &pair)?;
+                state.tpa_pair = true;
             }
-            println!("Memory usage: {} Mb", format_number(get_memory_usage()?));
         }
 
-        state.prepare_ask(&self.params)?;
-
-        let z: DMatrix<f32> = self.ask_z(state)?.z;
-
-        let eig_vals_sqrt: DMatrix<f32> = DMatrix::from_diagonal(
-            &state
-                .eig_vals
-                .iter()
-                .map(|x| x.sqrt())
-                .collect::<Vec<f32>>()
-                .into(),
-        );
-
-        // println!();
-        // println!("eig_vals_sqrt {:?}", &eig_vals_sqrt.data);
-        // println!("z {:?}", &z.data);
-        // print!("sigma {:?} ", &state.sigma);
-        // io::stdout().flush().unwrap();
-
-        let scaled_z: DMatrix<f32> = z.map(|x| x * state.sigma) * &eig_vals_sqrt;
-        // println!("z * sigma * eig_vals_sqrt {:?}", &scaled_z.data);
-
-        let rotated_z: DMatrix<f32> = scaled_z * &state.eig_vecs.transpose();
-        // println!("z * sigma * eig_vals_sqrt * eig_vecs.t {:?}", &rotated_z.data);
-
-        let y: DMatrix<f32> = DMatrix::from_rows(
-            &rotated_z
-                .row_iter()
-                .map(|row| row + &state.mean.transpose())
-                .collect::<Vec<_>>(),
-        );
-
-        state.y.copy_from(&y);
-
-        Ok(PopulationY { y })
+        #[cfg(feature = "tracing")]
+        tracing::trace!(popsize = pop.y.nrows(), sigma = state.sigma, "sampled");
+        Ok(pop)
     }
 
     /// TELL
@@ -209,47 +512,30 @@ impl CmaesAlgoOptimizer for CmaesAlgo {
     ///
     /// assert!(state.is_ok());
     /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "tell", level = "debug", skip_all, fields(g = state.g + 1))
+    )]
     fn tell(
         &self,
         mut state: CmaesState,
         pop: &mut PopulationY,
         fitness: &mut Fitness,
     ) -> Result<Self::NewState> {
+        let _timer = PhaseTimer::start(Phase::Update);
+
         // Init data
         state.g += 1;
         state.evals_count += fitness.values.nrows() as i32;
         let xold = state.mean.to_owned();
 
-        // Sort fitness values and population
-        let mut indices: Vec<usize> = (0..fitness.values.nrows()).collect(); // refactor
-        indices.sort_by(|&i, &j| {
-            fitness.values[(i, 0)]
-                .partial_cmp(&fitness.values[(j, 0)])
-                .unwrap()
-        });
-        let sorted_xs: DMatrix<f32> =
-            DMatrix::from_rows(&indices.iter().map(|&i| pop.y.row(i)).collect::<Vec<_>>());
-
-        let sorted_fit: DVector<f32> = DVector::from_rows(
-            &indices
-                .iter()
-                .map(|&i| fitness.values.row(i))
-                .collect::<Vec<_>>(),
-        );
-        pop.y.copy_from(&sorted_xs);
-        fitness.values.copy_from(&sorted_fit);
-
-        // Record current best solution, update best solution if any
-        // println!("{}", &fitness.values);
-        // println!("{}", &pop.y);
-        state.best_y_hist.push(fitness.values.rows(0, 2).mean());
-        if fitness.values[0] < state.best_y_fit[0] {
-            state.best_y.copy_from(&pop.y.row(0).transpose());
-            state.best_y_fit.copy_from(&fitness.values.row(0));
-        }
+        // Sort fitness values and population, record best solution if any
+        let indices = rank_population(&mut state, pop, fitness);
+        let steps = clipped_steps(&state.injected, &pop.y);
+        state.injected.clear();
 
         // Update mean
-        let y_mu: DMatrix<f32> = pop.y.rows(0, self.params.mu as usize).into();
+        let y_mu: DMatrix<f32> = steps.rows(0, self.params.mu as usize).into();
         let weights_mu: DVector<f32> = self.params.weights.rows(0, self.params.mu as usize).into(); // refactor as weights seems to not change at all, already in params
         let y_w: DVector<f32> = y_mu.transpose() * weights_mu;
         state.mean.copy_from(&y_w); // refactor less allocation
@@ -265,38 +551,77 @@ impl CmaesAlgoOptimizer for CmaesAlgo {
         let ccn = (self.params.cc * (2. - self.params.cc) * self.params.mueff).sqrt() / state.sigma;
         let hsig = state.ps.map(|x| x * x).sum()
             / (state.ps.len() as f32)
-            / (1. - (1. - self.params.cs).powi(2 * state.evals_count / self.params.popsize));
-        let new_pc = &state.pc * (1. - self.params.cs) + ccn * hsig * &new_y;
+            / (1. - (1. - self.params.cs).powi(2 * state.g))
+            < 2. + 4. / (self.params.n + 1.);
+        let hsig = if hsig { 1.0 } else { 0.0 };
+        let new_pc = &state.pc * (1. - self.params.cc) + ccn * hsig * &new_y;
         state.pc.copy_from(&new_pc);
 
         // Adapt covariance matrix C
         let c1a =
             self.params.c1 * (1. - (1. - hsig * hsig) * self.params.cc * (2. - self.params.cc));
-        state.cov = state.cov.map(|x| x * (1. - c1a - self.params.cmu));
+        let w_sum: f32 = self.params.weights.sum();
+        state.cov = state.cov.map(|x| x * (1. - c1a - self.params.cmu * w_sum));
         let pc_outer: DMatrix<f32> = &state.pc * &state.pc.transpose().map(|x| x * self.params.c1);
         state.cov = &state.cov + pc_outer;
 
-        // Perform the rank-mu update
-        // refactor for
+        // Perform the rank-mu update, steps relative to the sampling sigma
+        let sigma_sq = state.sigma * state.sigma;
         state.cov = self.params.weights.iter().enumerate().fold(
             state.cov.clone(), // Start with the initial covariance matrix
             |mut cov, (i, &w)| {
-                let w = if w < 0.0 { 0.001 } else { w }; // Ensure `w` is non-negative
-                let dx: DVector<f32> = &pop.y.rows(i, 1).transpose() - &xold;
+                let dx: DVector<f32> = &steps.rows(i, 1).transpose() - &xold;
                 let dx: DMatrix<f32> = &dx * &dx.transpose();
-                let dx: DMatrix<f32> =
-                    dx.map(|x| x * w * self.params.cmu / (self.params.sigma * self.params.sigma));
+                let dx: DMatrix<f32> = dx.map(|x| x * w * self.params.cmu / sigma_sq);
                 cov += dx; // Update the accumulated covariance
                 cov
             },
         );
 
         // Perform step-size sigma update
-        let cn = self.params.cs / self.params.damps;
-        let sum_square_ps = state.ps.map(|x| x * x).sum();
-        let other = cn * (sum_square_ps / self.params.n - 1.) / 2.;
-        state.sigma *= f32::min(1.0, other).exp();
-
+        let lam = fitness.values.nrows() as f32;
+        match self.params.step_size_rule {
+            StepSizeRule::Csa => {
+                let cn = self.params.cs / self.params.damps;
+                let sum_square_ps = state.ps.map(|x| x * x).sum();
+                let other = cn * (sum_square_ps / self.params.n - 1.) / 2.;
+                state.sigma *= f32::min(1.0, other).exp();
+            }
+            StepSizeRule::Tpa => {
+                // Rank difference of the two candidates placed by ask
+                if state.tpa_pair {
+                    let rank = |k: usize| indices.iter().position(|&i| i == k).unwrap() as f32;
+                    let z = (rank(1) - rank(0)) / (lam - 1.);
+                    let z = z.signum() * z.abs().sqrt();
+                    state.step_size_s = 0.7 * state.step_size_s + 0.3 * z;
+                    state.sigma *= (state.step_size_s / self.params.n.sqrt()).exp();
+                }
+            }
+            StepSizeRule::MedianSuccess => {
+                // Successes against the 30%-ile of the previous generation
+                if state.prev_fit.len() == fitness.values.len() {
+                    let f_ref = state.prev_fit[(0.3 * lam) as usize];
+                    let k_succ = fitness.values.iter().filter(|&&f| f < f_ref).count() as f32;
+                    let z = 2. / lam * (k_succ - (lam + 1.) / 2.);
+                    state.step_size_s = 0.7 * state.step_size_s + 0.3 * z;
+                    let damps = (2. - 2. / self.params.n).max(1.);
+                    state.sigma *= (state.step_size_s / damps).exp();
+                }
+                state.prev_fit = fitness.values.clone();
+            }
+        }
+        state.prev_mean = xold;
+        state.tpa_pair = false;
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            g = state.g,
+            evals = state.evals_count,
+            sigma = state.sigma,
+            best_fitness = state.best_y_fit[0],
+            generation_best = fitness.values[0],
+            "updated"
+        );
         Ok(state)
     }
 
@@ -322,34 +647,7 @@ impl CmaesAlgoOptimizer for CmaesAlgo {
     /// ```
     ///
     fn is_done(&self, state: &CmaesState, step: i32) -> Result<Self::Done> {
-        ////////////////
-        // TODO
-        // Dynamic how many historicals to average
-        ////////////////
-        let best_y_avg = if state.best_y_hist.len() > 10 {
-            let data = state.best_y_hist[state.best_y_hist.len() - 10..].to_vec();
-            DVector::from_vec(data).mean()
-            // median(data)
-        } else {
-            let data = state.best_y_hist[..].to_vec();
-            DVector::from_vec(data).mean()
-            // median(data)
-        };
-
-        // println!("Best y fit GLOBAL {:?}", state.best_y_fit.row(0)[0]);
-        // println!("Fit Hist (avg) {:?}", &state.best_y_hist);
-        // println!();
-
-        ////////////////
-        // TODO
-        // Dynamic how steps to require
-        ////////////////
-        if (step > 5) & ((state.best_y_fit.row(0)[0] - best_y_avg).abs() < self.params.tol) {
-            // println!("\n===> Search stopped due to tolerance of closeness change met");
-            Ok(true)
-        } else {
-            Ok(false)
-        }
+        Ok(tolerance_reached(state, self.params.tol, step) || evals_spent(state, &self.params))
     }
 
     fn rollout_fold(
@@ -357,11 +655,92 @@ impl CmaesAlgoOptimizer for CmaesAlgo {
         state: CmaesState,
         objective_function: impl FitnessFunction,
     ) -> Result<CmaesState> {
-        let final_state = (0..self.params.num_gens).fold(state, |mut state, _| {
-            let mut pop = self.ask(&mut state).unwrap();
-            let mut fitness = objective_function.evaluate(&pop).unwrap();
-            self.tell(state, &mut pop, &mut fitness).unwrap()
-        });
-        Ok(final_state)
+        self.rollout_observed(state, objective_function, self.params.num_gens, &mut ())
+    }
+}
+
+/// Candidates entering the update, with injected solutions replaced by their
+/// clipped counterparts.
+fn clipped_steps<'a>(
+    injected: &[(DVector<f32>, DVector<f32>)],
+    y: &'a DMatrix<f32>,
+) -> Cow<'a, DMatrix<f32>> {
+    if injected.is_empty() {
+        return Cow::Borrowed(y);
+    }
+    let mut steps = y.clone();
+    for mut row in steps.row_iter_mut() {
+        if let Some((_, clipped)) = injected.iter().find(|(x, _)| row == x.transpose()) {
+            row.copy_from(&clipped.transpose());
+        }
+    }
+    Cow::Owned(steps)
+}
+
+/// Sorts population and fitness values in place, NaN last, records the best
+/// solution and returns the sorting indices.
+pub(crate) fn rank_population(
+    state: &mut CmaesState,
+    pop: &mut PopulationY,
+    fitness: &mut Fitness,
+) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..fitness.values.nrows()).collect(); // refactor
+    indices.sort_by(|&i, &j| {
+        let (a, b) = (fitness.values[(i, 0)], fitness.values[(j, 0)]);
+        a.is_nan().cmp(&b.is_nan()).then(a.total_cmp(&b))
+    });
+    let sorted_xs: DMatrix<f32> =
+        DMatrix::from_rows(&indices.iter().map(|&i| pop.y.row(i)).collect::<Vec<_>>());
+
+    let sorted_fit: DVector<f32> = DVector::from_rows(
+        &indices
+            .iter()
+            .map(|&i| fitness.values.row(i))
+            .collect::<Vec<_>>(),
+    );
+    pop.y.copy_from(&sorted_xs);
+    fitness.values.copy_from(&sorted_fit);
+
+    // Record current best solution, update best solution if any
+    let top = fitness.values.nrows().min(2);
+    state.best_y_hist.push(fitness.values.rows(0, top).mean());
+    if fitness.values[0] < state.best_y_fit[0] {
+        state.best_y.copy_from(&pop.y.row(0).transpose());
+        state.best_y_fit.copy_from(&fitness.values.row(0));
+    }
+
+    indices
+}
+
+/// Whether the run used up the evaluation budget of `params`.
+pub(crate) fn evals_spent(state: &CmaesState, params: &CmaesParams) -> bool {
+    params.max_evals.is_some_and(|m| state.evals_count >= m)
+}
+
+/// Whether the best fitness is within `tol` of the recent historical average.
+pub(crate) fn tolerance_reached(state: &CmaesState, tol: f32, step: i32) -> bool {
+    ////////////////
+    // TODO
+    // Dynamic how many historicals to average
+    ////////////////
+    let best_y_avg = if state.best_y_hist.len() > 10 {
+        let data = state.best_y_hist[state.best_y_hist.len() - 10..].to_vec();
+        DVector::from_vec(data).mean()
+        // median(data)
+    } else {
+        let data = state.best_y_hist[..].to_vec();
+        DVector::from_vec(data).mean()
+        // median(data)
+    };
+
+    ////////////////
+    // TODO
+    // Dynamic how steps to require
+    ////////////////
+    let reached = (step > 5) & ((state.best_y_fit.row(0)[0] - best_y_avg).abs() < tol);
+    #[cfg(feature = "tracing")]
+    if reached {
+        tracing::debug!(g = state.g, best_y_avg, "tolerance reached");
     }
+    reached
 }
@@ -0,0 +1,347 @@
+use crate::fitness::{Fitness, FitnessEvaluator, FitnessFunction, PopulationY};
+use crate::state::CmaesState;
+use crate::strategy::{Budget, CmaesAlgo, CmaesAlgoOptimizer};
+use crate::utils::kendall_tau;
+use anyhow::Result;
+use nalgebra::{DMatrix, DVector};
+
+/// Kind of linear-quadratic model fitted on the archive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LqModelKind {
+    Linear,
+    DiagQuadratic,
+    FullQuadratic,
+}
+
+impl LqModelKind {
+    /// Number of coefficients of the model in dimension `n`.
+    ///
+    /// ```rust
+    /// use haru_cmaes::surrogate::LqModelKind;
+    ///
+    /// assert_eq!(LqModelKind::Linear.dof(3), 4);
+    /// assert_eq!(LqModelKind::DiagQuadratic.dof(3), 7);
+    /// assert_eq!(LqModelKind::FullQuadratic.dof(3), 10);
+    /// ```
+    pub fn dof(&self, n: usize) -> usize {
+        match self {
+            LqModelKind::Linear => n + 1,
+            LqModelKind::DiagQuadratic => 2 * n + 1,
+            LqModelKind::FullQuadratic => (n + 1) * (n + 2) / 2,
+        }
+    }
+
+    /// Richest model that `m` data points can support, if any.
+    ///
+    /// ```rust
+    /// use haru_cmaes::surrogate::LqModelKind;
+    ///
+    /// assert_eq!(LqModelKind::for_data(3, 2), None);
+    /// assert_eq!(LqModelKind::for_data(3, 5), Some(LqModelKind::Linear));
+    /// assert_eq!(LqModelKind::for_data(3, 11), Some(LqModelKind::FullQuadratic));
+    /// ```
+    pub fn for_data(n: usize, m: usize) -> Option<Self> {
+        // Require ~10% more points than coefficients, i.e. no plain interpolation
+        [
+            LqModelKind::FullQuadratic,
+            LqModelKind::DiagQuadratic,
+            LqModelKind::Linear,
+        ]
+        .into_iter()
+        .find(|kind| m as f32 >= (kind.dof(n) as f32 * 1.1).ceil())
+    }
+}
+
+/// Linear-quadratic surrogate model fitted by least squares.
+///
+/// Features are computed in the coordinate system of the current search
+/// distribution, i.e. `z = C^(-1/2) (x - mean) / sigma`, which keeps the
+/// regression well conditioned.
+#[derive(Debug, Clone)]
+pub struct LqModel {
+    pub kind: LqModelKind,
+    pub coefs: DVector<f32>,
+    pub center: DVector<f32>,
+    pub transform: DMatrix<f32>,
+}
+
+impl LqModel {
+    /// Fits a model of the given kind on the rows of `xs` and values `fs`.
+    ///
+    /// ```rust
+    /// use haru_cmaes::surrogate::{LqModel, LqModelKind};
+    /// use nalgebra::{DMatrix, DVector};
+    ///
+    /// let xs = DMatrix::from_row_slice(5, 2, &[
+    ///     0.0, 0.0,
+    ///     1.0, 0.0,
+    ///     0.0, 1.0,
+    ///     1.0, 1.0,
+    ///     2.0, 1.0,
+    /// ]);
+    /// let fs = DVector::from_vec(vec![1.0, 3.0, 0.0, 2.0, 4.0]);
+    /// let model = LqModel::fit(
+    ///     LqModelKind::Linear,
+    ///     &xs,
+    ///     &fs,
+    ///     DVector::zeros(2),
+    ///     DMatrix::identity(2, 2),
+    /// ).unwrap();
+    /// let pred = model.predict(&xs);
+    ///
+    /// assert!((pred - fs).norm() < 1e-3);
+    /// ```
+    pub fn fit(
+        kind: LqModelKind,
+        xs: &DMatrix<f32>,
+        fs: &DVector<f32>,
+        center: DVector<f32>,
+        transform: DMatrix<f32>,
+    ) -> Result<Self> {
+        let mut model = LqModel {
+            kind,
+            coefs: DVector::zeros(kind.dof(xs.ncols())),
+            center,
+            transform,
+        };
+        let phi = model.design_matrix(xs);
+        model.coefs = phi
+            .svd(true, true)
+            .solve(fs, 1e-6)
+            .map_err(|e| anyhow::anyhow!(e))?;
+        Ok(model)
+    }
+
+    /// Predicts the fitness of each row of `xs`.
+    pub fn predict(&self, xs: &DMatrix<f32>) -> DVector<f32> {
+        self.design_matrix(xs) * &self.coefs
+    }
+
+    fn design_matrix(&self, xs: &DMatrix<f32>) -> DMatrix<f32> {
+        let n = xs.ncols();
+        let dof = self.kind.dof(n);
+        let mut phi = DMatrix::zeros(xs.nrows(), dof);
+        for (r, row) in xs.row_iter().enumerate() {
+            let z: DVector<f32> = &self.transform * (row.transpose() - &self.center);
+            phi[(r, 0)] = 1.0;
+            for i in 0..n {
+                phi[(r, 1 + i)] = z[i];
+            }
+            match self.kind {
+                LqModelKind::Linear => {}
+                LqModelKind::DiagQuadratic => {
+                    for i in 0..n {
+                        phi[(r, 1 + n + i)] = z[i] * z[i];
+                    }
+                }
+                LqModelKind::FullQuadratic => {
+                    let mut col = 1 + n;
+                    for i in 0..n {
+                        for j in i..n {
+                            phi[(r, col)] = z[i] * z[j];
+                            col += 1;
+                        }
+                    }
+                }
+            }
+        }
+        phi
+    }
+}
+
+/// Archive of truly evaluated points.
+#[derive(Debug, Clone, Default)]
+pub struct SurrogateArchive {
+    pub xs: Vec<DVector<f32>>,
+    pub fs: Vec<f32>,
+}
+
+impl SurrogateArchive {
+    pub fn push(&mut self, x: DVector<f32>, f: f32) {
+        self.xs.push(x);
+        self.fs.push(f);
+    }
+
+    pub fn len(&self) -> usize {
+        self.fs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fs.is_empty()
+    }
+
+    /// Most recent `m` points as a row matrix and a value vector.
+    pub fn recent(&self, m: usize) -> (DMatrix<f32>, DVector<f32>) {
+        let start = self.len() - m.min(self.len());
+        let rows: Vec<_> = self.xs[start..].iter().map(|x| x.transpose()).collect();
+        (
+            DMatrix::from_rows(&rows),
+            DVector::from_column_slice(&self.fs[start..]),
+        )
+    }
+}
+
+/// Surrogate-assisted CMA-ES (lq-CMA-ES).
+///
+/// Each generation, a linear-quadratic model fitted on the archive ranks
+/// the population and only the most promising candidates are truly
+/// evaluated, batch by batch, until the model ranking agrees with the true
+/// one (Kendall tau above `tau_threshold`).
+///
+/// ```rust
+/// use haru_cmaes::fitness::MinOrMax;
+/// use haru_cmaes::objectives::SquareAndSum;
+/// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+/// use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+/// use haru_cmaes::strategy::CmaesAlgo;
+/// use haru_cmaes::surrogate::LqCmaes;
+///
+/// let obj_func = SquareAndSum { obj_dim: 3, dir: MinOrMax::Min };
+/// let params = CmaesParams::new()
+///     .and_then(|p| p.set_xstart(3, 1.0))
+///     .and_then(|p| p.set_num_gens(30))
+///     .unwrap();
+/// let cmaes = CmaesAlgo::new(params).unwrap();
+/// let state = CmaesState::init_state(&cmaes.params).unwrap();
+/// let mut lq = LqCmaes::new(cmaes, obj_func).unwrap();
+/// let state = lq.rollout_fold(state).unwrap();
+///
+/// // Fewer true evaluations than sampled candidates
+/// let sampled = state.g * lq.cmaes.params.popsize;
+/// assert!(state.evals_count < sampled);
+/// assert!(state.best_y_fit[0].is_finite());
+/// ```
+pub struct LqCmaes<F: FitnessFunction> {
+    pub cmaes: CmaesAlgo,
+    pub objective: F,
+    pub archive: SurrogateArchive,
+    pub tau_threshold: f32,
+}
+
+impl<F: FitnessFunction> LqCmaes<F> {
+    pub fn new(cmaes: CmaesAlgo, objective: F) -> Result<Self> {
+        Ok(Self {
+            cmaes,
+            objective,
+            archive: SurrogateArchive::default(),
+            tau_threshold: 0.85,
+        })
+    }
+
+    /// Fits the richest model the archive supports around the current distribution.
+    fn fit_model(&self, state: &CmaesState) -> Result<Option<LqModel>> {
+        let n = state.mean.len();
+        let max_train =
+            (2 * LqModelKind::FullQuadratic.dof(n)).max(self.cmaes.params.popsize as usize);
+        let m = self.archive.len().min(max_train);
+        match LqModelKind::for_data(n, m) {
+            Some(kind) => {
+                let (xs, fs) = self.archive.recent(m);
+                let transform = state.inv_sqrt.map(|x| x / state.sigma);
+                LqModel::fit(kind, &xs, &fs, state.mean.clone(), transform).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Truly evaluates the given rows of the population and archives them.
+    fn evaluate_rows(&mut self, pop: &PopulationY, rows: &[usize]) -> Result<Vec<f32>> {
+        let sub = PopulationY {
+            y: DMatrix::from_rows(&rows.iter().map(|&i| pop.y.row(i)).collect::<Vec<_>>()),
+        };
+        let fitness = self.objective.evaluate(&sub)?;
+        rows.iter()
+            .zip(fitness.values.iter())
+            .for_each(|(&i, &f)| self.archive.push(pop.y.row(i).transpose(), f));
+        Ok(fitness.values.iter().copied().collect())
+    }
+
+    /// Runs one surrogate-assisted generation: ask, rank, evaluate, tell.
+    pub fn step(&mut self, mut state: CmaesState) -> Result<CmaesState> {
+        let mut pop = self.cmaes.ask(&mut state)?;
+        let lam = pop.y.nrows();
+        let batch = (lam / 10).max(1);
+        let mut values: Vec<Option<f32>> = vec![None; lam];
+        let mut n_evaluated = 0;
+
+        while n_evaluated < lam {
+            let pending: Vec<usize> = (0..lam).filter(|&i| values[i].is_none()).collect();
+            let model = match self.fit_model(&state)? {
+                Some(model) => model,
+                None => {
+                    // Not enough data for any model yet, evaluate everything
+                    let fs = self.evaluate_rows(&pop, &pending)?;
+                    pending
+                        .iter()
+                        .zip(fs)
+                        .for_each(|(&i, f)| values[i] = Some(f));
+                    n_evaluated = lam;
+                    break;
+                }
+            };
+
+            // Evaluate the next batch of most promising candidates
+            let pred = model.predict(&pop.y);
+            let mut order = pending;
+            order.sort_by(|&i, &j| pred[i].total_cmp(&pred[j]));
+            let next: Vec<usize> = order.into_iter().take(batch).collect();
+            let fs = self.evaluate_rows(&pop, &next)?;
+            next.iter().zip(fs).for_each(|(&i, f)| values[i] = Some(f));
+            n_evaluated += next.len();
+
+            // Stop once the (pre-batch) model ranks the latest points well enough
+            let n_tau = ((1.2 * n_evaluated as f32) as usize)
+                .min((0.75 * lam as f32) as usize)
+                .max(15)
+                .min(self.archive.len());
+            let (xs, fs) = self.archive.recent(n_tau);
+            let tau = kendall_tau(model.predict(&xs).as_slice(), fs.as_slice());
+            if tau >= self.tau_threshold {
+                break;
+            }
+        }
+
+        // Remaining candidates are ranked by the surrogate, behind the evaluated ones
+        if n_evaluated < lam {
+            if let Some(model) = self.fit_model(&state)? {
+                let pred = model.predict(&pop.y);
+                let max_true = values.iter().flatten().fold(f32::MIN, |a, &b| a.max(b));
+                let min_pred = (0..lam)
+                    .filter(|&i| values[i].is_none())
+                    .fold(f32::MAX, |a, i| a.min(pred[i]));
+                values
+                    .iter_mut()
+                    .enumerate()
+                    .filter(|(_, v)| v.is_none())
+                    .for_each(|(i, v)| *v = Some(pred[i] - min_pred + max_true));
+            }
+        }
+
+        let mut fitness = Fitness {
+            values: DVector::from_iterator(lam, values.into_iter().map(|v| v.unwrap())),
+        };
+        let mut state = self.cmaes.tell(state, &mut pop, &mut fitness)?;
+        // Tell counts the whole population, only the true evaluations matter here
+        state.evals_count -= (lam - n_evaluated) as i32;
+        Ok(state)
+    }
+
+    /// Runs `num_gens` surrogate-assisted generations, or fewer within the
+    /// budget of the parameters.
+    ///
+    /// A generation evaluates up to the whole population, so the run stops
+    /// once the evaluations left could not cover one.
+    pub fn rollout_fold(&mut self, mut state: CmaesState) -> Result<CmaesState> {
+        let mut budget = Budget::from_params(&self.cmaes.params, &state);
+        let popsize = self.cmaes.params.popsize as usize;
+        for _ in 0..self.cmaes.params.num_gens {
+            if budget.exhausted().is_some() || budget.candidates(popsize) < popsize {
+                break;
+            }
+            let evals = state.evals_count;
+            state = self.step(state)?;
+            budget.spend((state.evals_count - evals) as usize);
+        }
+        Ok(state)
+    }
+}
@@ -0,0 +1,245 @@
+use crate::fitness::{Fitness, PopulationY};
+use crate::params::StepSizeRule;
+use crate::state::CmaesState;
+use crate::strategy::{Budget, CmaesAlgoOptimizer};
+use anyhow::{ensure, Context, Result};
+use nalgebra::{DMatrix, DVector};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// What to hand out once every candidate of a generation is out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stragglers {
+    Resample,          // Fresh samples of the generation; the first results win
+    Reissue(Duration), // Candidates whose tickets are this old, under new tickets
+    Wait,              // Nothing until the generation is complete
+}
+
+/// One candidate to evaluate, handed out by `TicketDriver::ask_one`.
+#[derive(Debug, Clone)]
+pub struct Ticket {
+    pub id: u64,
+    pub g: i32, // Generation the candidate belongs to, counting from 0
+    pub x: DVector<f32>,
+}
+
+/// What `TicketDriver::submit` did with a result.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Submission {
+    Pending, // Recorded, the generation needs more results
+    Told,    // Completed the generation or spent the budget, and told it
+    Stale,   // Dropped, its generation was told or its candidate already evaluated
+}
+
+/// Candidate of the current generation.
+#[derive(Debug, Clone)]
+struct Slot {
+    x: DVector<f32>,
+    value: Option<f32>,
+    issued: Option<Instant>, // Last time it was handed out
+}
+
+/// Ask/tell driver for evaluations that finish in any order.
+///
+/// Candidates are handed out one at a time as tickets, and their fitness
+/// (minimized) is submitted with the ticket id whenever it is ready, from
+/// a job queue, threads or async tasks. The generation is told once
+/// `popsize` results are in, in sampling order, and the next one sampled.
+/// `stragglers` sets what is handed out while results are missing; results
+/// arriving after their generation was told are dropped as `Stale`.
+///
+/// Every result of a handed-out ticket counts in `evals_count` and against
+/// the budget of the parameters, stale ones included. No ticket is handed
+/// out beyond the evaluations left, counting those in flight, nor past the
+/// deadline; the generation is told truncated once the budget is spent.
+///
+/// ```rust
+/// use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+/// use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+/// use haru_cmaes::strategy::CmaesAlgo;
+/// use haru_cmaes::tickets::{Stragglers, Submission, TicketDriver};
+///
+/// let params = CmaesParams::new().and_then(|p| p.set_xstart(3, 1.0)).unwrap();
+/// let cmaes = CmaesAlgo::new(params).unwrap();
+/// let state = CmaesState::init_state(&cmaes.params).unwrap();
+/// let mut driver = TicketDriver::new(cmaes, state, Stragglers::Resample).unwrap();
+///
+/// let sphere = |x: &nalgebra::DVector<f32>| x.norm_squared();
+/// let mut in_flight = Vec::new();
+/// let mut stale = 0;
+/// while driver.state().g < 100 {
+///     // Keep 12 evaluations running, finishing in random order
+///     while in_flight.len() < 12 {
+///         in_flight.push(driver.ask_one().unwrap().unwrap());
+///     }
+///     let ticket = in_flight.swap_remove(fastrand::usize(..in_flight.len()));
+///     if driver.submit(ticket.id, sphere(&ticket.x)).unwrap() == Submission::Stale {
+///         stale += 1;
+///     }
+/// }
+///
+/// assert!(stale > 0);
+/// assert!(driver.state().best_y_fit[0] < 1e-4);
+/// ```
+pub struct TicketDriver<A: CmaesAlgoOptimizer> {
+    pub cmaes: A,
+    pub stragglers: Stragglers,
+    pub budget: Budget,        // Left of the parameters' budget, from `new`
+    state: Option<CmaesState>, // Only missing after a failed `tell`
+    slots: Vec<Slot>,
+    tickets: HashMap<u64, usize>, // Open tickets of the generation, to their slot
+    late: HashSet<u64>,           // Open tickets of told generations
+    results: usize,
+    next_id: u64,
+}
+
+impl<A> TicketDriver<A>
+where
+    A: CmaesAlgoOptimizer<NewPopulation = PopulationY, NewState = CmaesState>,
+{
+    /// Samples the first generation of `state`.
+    pub fn new(cmaes: A, state: CmaesState, stragglers: Stragglers) -> Result<Self> {
+        ensure!(
+            stragglers != Stragglers::Resample
+                || cmaes.params().step_size_rule != StepSizeRule::Tpa,
+            "Two-point adaptation needs its first two candidates, which resampling may drop"
+        );
+        let budget = Budget::from_params(cmaes.params(), &state);
+        let mut driver = Self {
+            cmaes,
+            stragglers,
+            budget,
+            state: Some(state),
+            slots: Vec::new(),
+            tickets: HashMap::new(),
+            late: HashSet::new(),
+            results: 0,
+            next_id: 0,
+        };
+        driver.sample()?;
+        Ok(driver)
+    }
+
+    /// State of the current generation.
+    pub fn state(&self) -> &CmaesState {
+        self.state.as_ref().expect("a failed tell left no state")
+    }
+
+    /// Takes back the state; answers still out are lost.
+    pub fn into_state(self) -> CmaesState {
+        self.state.expect("a failed tell left no state")
+    }
+
+    /// Tickets handed out and not yet answered in the current generation.
+    pub fn in_flight(&self) -> usize {
+        self.tickets.len()
+    }
+
+    /// Adds a freshly sampled population to the generation.
+    fn sample(&mut self) -> Result<()> {
+        let state = self.state.as_mut().context("A failed tell left no state")?;
+        let pop = self.cmaes.ask(state)?;
+        self.slots.extend(pop.y.row_iter().map(|row| Slot {
+            x: row.transpose(),
+            value: None,
+            issued: None,
+        }));
+        Ok(())
+    }
+
+    /// Hands out the next candidate, or `None` while waiting for results or
+    /// once the budget is spent.
+    pub fn ask_one(&mut self) -> Result<Option<Ticket>> {
+        let open = self.tickets.len() + self.late.len();
+        if self.budget.exhausted().is_some() || self.budget.candidates(open + 1) <= open {
+            return Ok(None);
+        }
+        let now = Instant::now();
+        let fresh = self.slots.iter().position(|s| s.issued.is_none());
+        let slot = match (fresh, self.stragglers) {
+            (Some(i), _) => i,
+            (None, Stragglers::Resample) => {
+                self.sample()?;
+                self.slots.iter().position(|s| s.issued.is_none()).unwrap()
+            }
+            (None, Stragglers::Reissue(after)) => {
+                let late =
+                    self.slots.iter().enumerate().find(|(_, s)| {
+                        s.value.is_none() && s.issued.is_some_and(|t| now - t >= after)
+                    });
+                match late {
+                    Some((i, _)) => i,
+                    None => return Ok(None),
+                }
+            }
+            (None, Stragglers::Wait) => return Ok(None),
+        };
+        self.slots[slot].issued = Some(now);
+        let id = self.next_id;
+        self.next_id += 1;
+        self.tickets.insert(id, slot);
+        Ok(Some(Ticket {
+            id,
+            g: self.state().g,
+            x: self.slots[slot].x.clone(),
+        }))
+    }
+
+    /// Records the fitness of ticket `id`, telling the generation once complete.
+    ///
+    /// Non-finite values are kept, `tell` ranks NaN last.
+    pub fn submit(&mut self, id: u64, value: f32) -> Result<Submission> {
+        let Some(slot) = self.tickets.remove(&id) else {
+            if self.late.remove(&id) {
+                return self.count_stale();
+            }
+            ensure!(id < self.next_id, "Unknown ticket {}", id);
+            // Answered before, its evaluation is already counted
+            return Ok(Submission::Stale);
+        };
+        if self.slots[slot].value.is_some() {
+            return self.count_stale();
+        }
+        self.slots[slot].value = Some(value);
+        self.results += 1;
+        self.budget.spend(1);
+        if self.results < self.cmaes.params().popsize as usize && self.budget.exhausted().is_none()
+        {
+            return Ok(Submission::Pending);
+        }
+        self.tell_generation()
+    }
+
+    /// Counts the evaluation behind a stale result, telling the results in
+    /// if it spent the budget.
+    fn count_stale(&mut self) -> Result<Submission> {
+        let state = self.state.as_mut().context("A failed tell left no state")?;
+        state.evals_count += 1;
+        self.budget.spend(1);
+        if self.results > 0 && self.budget.exhausted().is_some() {
+            return self.tell_generation();
+        }
+        Ok(Submission::Stale)
+    }
+
+    /// Tells the results of the generation, truncated if the budget is
+    /// spent, and samples the next one while budget is left.
+    fn tell_generation(&mut self) -> Result<Submission> {
+        let done: Vec<&Slot> = self.slots.iter().filter(|s| s.value.is_some()).collect();
+        let mut pop = PopulationY {
+            y: DMatrix::from_rows(&done.iter().map(|s| s.x.transpose()).collect::<Vec<_>>()),
+        };
+        let mut fitness = Fitness {
+            values: DVector::from_iterator(done.len(), done.iter().map(|s| s.value.unwrap())),
+        };
+        let state = self.state.take().context("A failed tell left no state")?;
+        self.state = Some(self.cmaes.tell_truncated(state, &mut pop, &mut fitness)?);
+        self.slots.clear();
+        self.late.extend(self.tickets.drain().map(|(id, _)| id));
+        self.results = 0;
+        if self.budget.exhausted().is_none() {
+            self.sample()?;
+        }
+        Ok(Submission::Told)
+    }
+}
@@ -0,0 +1,310 @@
+use crate::fitness::{FitnessFunction, MinOrMax, PopulationY};
+use crate::objectives::KnownOptimum;
+use crate::utils::{random_rotation, standard_normal};
+use anyhow::{ensure, Result};
+use nalgebra::{DMatrix, DVector};
+
+/// Translation of the search space, the optimum moves by `shift`.
+///
+/// ```rust
+/// use haru_cmaes::objectives::{KnownOptimum, SquareAndSum};
+/// use haru_cmaes::transforms::Shifted;
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::{DMatrix, DVector};
+///
+/// let inner = SquareAndSum { obj_dim: 2, dir: MinOrMax::Min };
+/// let objective_function = Shifted { inner, shift: DVector::from_vec(vec![1.0, -2.0]) };
+/// let y = DMatrix::from_row_slice(1, 2, objective_function.optimum_x().as_slice());
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+///
+/// assert_eq!(objective_function.optimum_x()[1], -2.0);
+/// assert_eq!(fitness.values[0], 0.0);
+/// ```
+pub struct Shifted<F: FitnessFunction> {
+    pub inner: F,
+    pub shift: DVector<f32>,
+}
+
+impl<F: FitnessFunction> FitnessFunction for Shifted<F> {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        let y = DMatrix::from_rows(
+            &pop.y
+                .row_iter()
+                .map(|row| row - self.shift.transpose())
+                .collect::<Vec<_>>(),
+        );
+        self.inner.cost(&PopulationY { y })
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.inner.cost_dim()
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        self.inner.optimization_type()
+    }
+}
+
+impl<F: FitnessFunction + KnownOptimum> KnownOptimum for Shifted<F> {
+    fn optimum_x(&self) -> DVector<f32> {
+        self.inner.optimum_x() + &self.shift
+    }
+
+    fn optimum_f(&self) -> f32 {
+        self.inner.optimum_f()
+    }
+}
+
+/// Rotation of the search space, `inner` is evaluated at `rotation * y`.
+///
+/// ```rust
+/// use haru_cmaes::objectives::{Ellipsoid, KnownOptimum};
+/// use haru_cmaes::transforms::{Rotated, Shifted};
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::{DMatrix, DVector};
+///
+/// let inner = Ellipsoid { obj_dim: 3, dir: MinOrMax::Min };
+/// let inner = Shifted { inner, shift: DVector::from_element(3, 1.0) };
+/// let objective_function = Rotated::random(inner);
+/// let rotation = &objective_function.rotation;
+///
+/// // Orthogonal, and the optimum is rotated back
+/// assert!((rotation.transpose() * rotation - DMatrix::identity(3, 3)).norm() < 1e-5);
+/// let y = DMatrix::from_row_slice(1, 3, objective_function.optimum_x().as_slice());
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+/// assert!(fitness.values[0] < 1e-4);
+/// ```
+pub struct Rotated<F: FitnessFunction> {
+    pub inner: F,
+    pub rotation: DMatrix<f32>,
+}
+
+impl<F: FitnessFunction> Rotated<F> {
+    /// Wraps `inner` with a uniformly drawn random orthogonal matrix.
+    pub fn random(inner: F) -> Self {
+        let rotation = random_rotation(inner.cost_dim());
+        Self { inner, rotation }
+    }
+}
+
+impl<F: FitnessFunction> FitnessFunction for Rotated<F> {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        let y = &pop.y * self.rotation.transpose();
+        self.inner.cost(&PopulationY { y })
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.inner.cost_dim()
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        self.inner.optimization_type()
+    }
+}
+
+impl<F: FitnessFunction + KnownOptimum> KnownOptimum for Rotated<F> {
+    fn optimum_x(&self) -> DVector<f32> {
+        self.rotation.transpose() * self.inner.optimum_x()
+    }
+
+    fn optimum_f(&self) -> f32 {
+        self.inner.optimum_f()
+    }
+}
+
+/// Per-axis scaling of the search space, `inner` is evaluated at `scales * y`.
+///
+/// ```rust
+/// use haru_cmaes::objectives::SquareAndSum;
+/// use haru_cmaes::transforms::Scaled;
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::DMatrix;
+///
+/// let inner = SquareAndSum { obj_dim: 3, dir: MinOrMax::Min };
+/// let objective_function = Scaled::conditioned(inner, 1e4).unwrap();
+/// let y = DMatrix::from_row_slice(1, 3, &[1.0, 1.0, 1.0]);
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+///
+/// // An ellipsoid with axis ratio 100
+/// assert_eq!(objective_function.scales[2], 100.0);
+/// assert!((fitness.values[0] - 10101.0).abs() < 1e-2);
+/// ```
+pub struct Scaled<F: FitnessFunction> {
+    pub inner: F,
+    pub scales: DVector<f32>,
+}
+
+impl<F: FitnessFunction> Scaled<F> {
+    /// Scales growing geometrically from `1` to `sqrt(condition)`, i.e. a
+    /// quadratic `inner` gets condition number `condition`.
+    pub fn conditioned(inner: F, condition: f32) -> Result<Self> {
+        ensure!(condition >= 1.0, "Condition number must be at least 1");
+        let n = inner.cost_dim();
+        let scales = DVector::from_fn(n, |i, _| {
+            let ratio = if n > 1 {
+                i as f32 / (n - 1) as f32
+            } else {
+                0.0
+            };
+            condition.sqrt().powf(ratio)
+        });
+        Ok(Self { inner, scales })
+    }
+}
+
+impl<F: FitnessFunction> FitnessFunction for Scaled<F> {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        let y = DMatrix::from_rows(
+            &pop.y
+                .row_iter()
+                .map(|row| row.component_mul(&self.scales.transpose()))
+                .collect::<Vec<_>>(),
+        );
+        self.inner.cost(&PopulationY { y })
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.inner.cost_dim()
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        self.inner.optimization_type()
+    }
+}
+
+impl<F: FitnessFunction + KnownOptimum> KnownOptimum for Scaled<F> {
+    fn optimum_x(&self) -> DVector<f32> {
+        self.inner.optimum_x().component_div(&self.scales)
+    }
+
+    fn optimum_f(&self) -> f32 {
+        self.inner.optimum_f()
+    }
+}
+
+/// Strictly increasing transformation `g` of the fitness values.
+///
+/// Rank-based strategies such as CMA-ES are invariant to it.
+///
+/// ```rust
+/// use haru_cmaes::objectives::{KnownOptimum, SquareAndSum};
+/// use haru_cmaes::transforms::Monotone;
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::DMatrix;
+///
+/// let inner = SquareAndSum { obj_dim: 2, dir: MinOrMax::Min };
+/// let objective_function = Monotone { inner, transform: |f: f32| (1.0 + f).ln() };
+/// let y = DMatrix::from_row_slice(2, 2, &[1.0, 1.0, 0.0, 0.0]);
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+///
+/// assert!((fitness.values[0] - 3f32.ln()).abs() < 1e-6);
+/// assert_eq!(objective_function.optimum_f(), 0.0);
+/// ```
+pub struct Monotone<F: FitnessFunction, G: Fn(f32) -> f32> {
+    pub inner: F,
+    pub transform: G,
+}
+
+impl<F: FitnessFunction, G: Fn(f32) -> f32> FitnessFunction for Monotone<F, G> {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        self.inner.cost(pop).map(&self.transform)
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.inner.cost_dim()
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        self.inner.optimization_type()
+    }
+}
+
+impl<F: FitnessFunction + KnownOptimum, G: Fn(f32) -> f32> KnownOptimum for Monotone<F, G> {
+    fn optimum_x(&self) -> DVector<f32> {
+        self.inner.optimum_x()
+    }
+
+    fn optimum_f(&self) -> f32 {
+        (self.transform)(self.inner.optimum_f())
+    }
+}
+
+/// Noise models for `Noisy`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoiseModel {
+    Additive(f32),       // f + std * N(0, 1)
+    Multiplicative(f32), // f * (1 + std * N(0, 1))
+}
+
+/// Fitness values perturbed by random noise on every evaluation.
+///
+/// The noiseless optimum is exposed, noise is drawn from the global `fastrand` generator.
+///
+/// ```rust
+/// use haru_cmaes::objectives::SquareAndSum;
+/// use haru_cmaes::transforms::{NoiseModel, Noisy};
+/// use haru_cmaes::fitness::{PopulationY, FitnessEvaluator};
+/// use haru_cmaes::fitness::MinOrMax;
+/// use nalgebra::DMatrix;
+///
+/// let inner = SquareAndSum { obj_dim: 2, dir: MinOrMax::Min };
+/// let objective_function = Noisy { inner, model: NoiseModel::Multiplicative(0.1) };
+/// let y = DMatrix::from_element(1000, 2, 1.0);
+/// let fitness = objective_function.evaluate(&PopulationY { y }).unwrap();
+///
+/// // Noisy around the true value 2
+/// assert!(fitness.values.min() < fitness.values.max());
+/// assert!((fitness.values.mean() - 2.0).abs() < 0.05);
+/// ```
+pub struct Noisy<F: FitnessFunction> {
+    pub inner: F,
+    pub model: NoiseModel,
+}
+
+impl<F: FitnessFunction> FitnessFunction for Noisy<F> {
+    // Required method
+    fn cost(&self, pop: &PopulationY) -> DVector<f32> {
+        let values = self.inner.cost(pop);
+        let noise = standard_normal(values.len(), 1).column(0).into_owned();
+        match self.model {
+            NoiseModel::Additive(std) => values + noise * std,
+            NoiseModel::Multiplicative(std) => values.component_mul(&noise.map(|e| 1.0 + std * e)),
+        }
+    }
+
+    // Required method
+    fn cost_dim(&self) -> usize {
+        self.inner.cost_dim()
+    }
+
+    // Required method
+    fn optimization_type(&self) -> &MinOrMax {
+        self.inner.optimization_type()
+    }
+}
+
+impl<F: FitnessFunction + KnownOptimum> KnownOptimum for Noisy<F> {
+    fn optimum_x(&self) -> DVector<f32> {
+        self.inner.optimum_x()
+    }
+
+    fn optimum_f(&self) -> f32 {
+        self.inner.optimum_f()
+    }
+}
@@ -1,3 +1,7 @@
+use nalgebra::DMatrix;
+
+// `is_multiple_of` needs Rust 1.87
+#[allow(clippy::manual_is_multiple_of)]
 pub fn median(mut data: Vec<f32>) -> f32 {
     data.sort_by(|a, b| a.partial_cmp(b).unwrap());
     let len = data.len();
@@ -9,3 +13,52 @@ pub fn median(mut data: Vec<f32>) -> f32 {
         data[len / 2]
     }
 }
+
+pub fn kendall_tau(a: &[f32], b: &[f32]) -> f32 {
+    // Tau-b variant, i.e. accounts for ties in either ranking
+    let len = a.len().min(b.len());
+    let (mut concordant, mut discordant) = (0.0_f32, 0.0_f32);
+    let (mut ties_a, mut ties_b) = (0.0_f32, 0.0_f32);
+    for i in 0..len {
+        for j in (i + 1)..len {
+            let da = a[i] - a[j];
+            let db = b[i] - b[j];
+            if da == 0.0 && db == 0.0 {
+                continue;
+            } else if da == 0.0 {
+                ties_a += 1.0;
+            } else if db == 0.0 {
+                ties_b += 1.0;
+            } else if da * db > 0.0 {
+                concordant += 1.0;
+            } else {
+                discordant += 1.0;
+            }
+        }
+    }
+    let denom = ((concordant + discordant + ties_a) * (concordant + discordant + ties_b)).sqrt();
+    if denom == 0.0 {
+        0.0
+    } else {
+        (concordant - discordant) / denom
+    }
+}
+
+pub fn standard_normal(nrows: usize, ncols: usize) -> DMatrix<f32> {
+    let data: Vec<f32> = (0..nrows * ncols)
+        .map(|_| {
+            // Convert uniform random numbers to standard normal distribution
+            let u1 = fastrand::f32();
+            let u2 = fastrand::f32();
+            (-2.0 * u1.clamp(0.0001, 0.9999).ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos()
+        })
+        .collect();
+    DMatrix::from_row_slice(nrows, ncols, &data)
+}
+
+pub fn random_rotation(n: usize) -> DMatrix<f32> {
+    // QR of a Gaussian matrix, with signs fixed so the draw is uniform (Haar)
+    let qr = standard_normal(n, n).qr();
+    let signs = qr.r().diagonal().map(|d| if d < 0.0 { -1.0 } else { 1.0 });
+    qr.q() * DMatrix::from_diagonal(&signs)
+}
@@ -0,0 +1,190 @@
+//! Statistical convergence regression tests.
+//!
+//! Each case runs seeded repetitions of CMA-ES and compares the success rate
+//! and the median number of evaluations of the successful runs with
+//! reference values recorded for this implementation. The success count must
+//! stay above a three standard deviation binomial bound of the reference
+//! rate, the median within a relative tolerance of the reference median.
+
+use haru_cmaes::fitness::{FitnessEvaluator, FitnessFunction, MinOrMax};
+use haru_cmaes::objectives::{Ellipsoid, Rastrigin, Rosenbrock, SquareAndSum};
+use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+use haru_cmaes::strategy::{CmaesAlgo, CmaesAlgoOptimizer};
+
+const REPETITIONS: u64 = 21;
+const MEDIAN_TOLERANCE: f64 = 0.25;
+
+struct Case {
+    dim: usize,
+    popsize: i32,
+    sigma: f32,
+    target: f32,
+    budget: usize,
+    success_rate: f64, // Reference fraction of successful runs
+    median_evals: f64, // Reference median evaluations of successful runs
+}
+
+impl Case {
+    /// Default population size `4 + 3 ln(n)`, start uniform in `[-2, 2]^n`.
+    fn new(dim: usize, target: f32, budget: usize) -> Self {
+        Self {
+            dim,
+            popsize: 4 + (3. * (dim as f32).ln()) as i32,
+            sigma: 1.0,
+            target,
+            budget,
+            success_rate: 1.0,
+            median_evals: 0.0,
+        }
+    }
+
+    fn popsize(self, popsize: i32) -> Self {
+        Self { popsize, ..self }
+    }
+
+    fn sigma(self, sigma: f32) -> Self {
+        Self { sigma, ..self }
+    }
+
+    fn reference(self, success_rate: f64, median_evals: f64) -> Self {
+        Self {
+            success_rate,
+            median_evals,
+            ..self
+        }
+    }
+}
+
+/// Evaluations until the target is reached, `None` if the budget runs out first.
+fn run(case: &Case, objective: &impl FitnessFunction, seed: u64) -> Option<usize> {
+    fastrand::seed(seed);
+    let mut params = CmaesParams::new()
+        .and_then(|p| p.set_popsize(case.popsize))
+        .and_then(|p| p.set_xstart(case.dim, 0.0))
+        .and_then(|p| p.set_sigma(case.sigma))
+        .unwrap();
+    params.xstart = (0..case.dim).map(|_| 4. * fastrand::f32() - 2.).collect();
+    let cmaes = CmaesAlgo::new(params).unwrap();
+    let mut state = CmaesState::init_state(&cmaes.params).unwrap();
+
+    let mut evals = 0;
+    while evals < case.budget {
+        let mut pop = cmaes.ask(&mut state).unwrap();
+        let mut fitness = objective.evaluate(&pop).unwrap();
+        evals += fitness.values.len();
+        assert!(
+            fitness.values.iter().all(|f| f.is_finite()),
+            "Non-finite fitness after {} evaluations, seed {}",
+            evals,
+            seed
+        );
+        if fitness.values.min() <= case.target {
+            return Some(evals);
+        }
+        state = cmaes.tell(state, &mut pop, &mut fitness).unwrap();
+        // Converged elsewhere, below f32 resolution of the solution
+        if state.sigma * state.eig_vals.max().sqrt() < 1e-9 {
+            return None;
+        }
+    }
+    None
+}
+
+fn check(name: &str, case: Case, objective: impl FitnessFunction) {
+    let mut evals: Vec<usize> = (0..REPETITIONS)
+        .filter_map(|seed| run(&case, &objective, seed))
+        .collect();
+    evals.sort();
+
+    let n = REPETITIONS as f64;
+    let p = case.success_rate;
+    let min_successes = (n * p - 3. * (n * p * (1. - p)).sqrt()).floor().max(1.);
+    assert!(
+        evals.len() as f64 >= min_successes,
+        "{} {}-D: {} of {} runs reached {:e}, expected at least {}",
+        name,
+        case.dim,
+        evals.len(),
+        REPETITIONS,
+        case.target,
+        min_successes
+    );
+
+    let median = evals[evals.len() / 2] as f64;
+    let deviation = (median - case.median_evals).abs() / case.median_evals;
+    assert!(
+        deviation <= MEDIAN_TOLERANCE,
+        "{} {}-D: median of {} evaluations, reference {}",
+        name,
+        case.dim,
+        median,
+        case.median_evals
+    );
+}
+
+#[test]
+fn sphere() {
+    for case in [
+        Case::new(2, 1e-8, 4000).reference(1.0, 282.),
+        Case::new(5, 1e-8, 10000).reference(1.0, 720.),
+        Case::new(10, 1e-8, 20000).reference(1.0, 1450.),
+    ] {
+        let objective = SquareAndSum {
+            obj_dim: case.dim,
+            dir: MinOrMax::Min,
+        };
+        check("sphere", case, objective);
+    }
+}
+
+#[test]
+fn ellipsoid() {
+    for case in [
+        Case::new(2, 1e-8, 10000).reference(1.0, 702.),
+        Case::new(5, 1e-8, 25000).reference(1.0, 2120.),
+        Case::new(10, 1e-8, 50000).reference(1.0, 5820.),
+    ] {
+        let objective = Ellipsoid {
+            obj_dim: case.dim,
+            dir: MinOrMax::Min,
+        };
+        check("ellipsoid", case, objective);
+    }
+}
+
+#[test]
+fn rosenbrock() {
+    for case in [
+        Case::new(2, 1e-6, 10000).reference(1.0, 468.),
+        Case::new(5, 1e-6, 25000).reference(0.94, 1920.),
+        Case::new(10, 1e-6, 50000).reference(0.98, 5850.),
+    ] {
+        let objective = Rosenbrock {
+            obj_dim: case.dim,
+            dir: MinOrMax::Min,
+        };
+        check("rosenbrock", case, objective);
+    }
+}
+
+#[test]
+fn rastrigin() {
+    // Larger populations find the global basin in a fraction of the runs
+    for case in [
+        Case::new(2, 1e-4, 20000)
+            .popsize(60)
+            .sigma(2.0)
+            .reference(0.73, 1140.),
+        Case::new(5, 1e-4, 50000)
+            .popsize(80)
+            .sigma(2.0)
+            .reference(0.51, 3680.),
+    ] {
+        let objective = Rastrigin {
+            obj_dim: case.dim,
+            dir: MinOrMax::Min,
+        };
+        check("rastrigin", case, objective);
+    }
+}
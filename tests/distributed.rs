@@ -0,0 +1,198 @@
+//! Runs the coordinator with local workers over TCP on localhost.
+#![cfg(feature = "distributed")]
+
+use haru_cmaes::distributed::{Coordinator, CoordinatorMessage, Worker, WorkerMessage};
+use haru_cmaes::fitness::{Fitness, PopulationY};
+use haru_cmaes::objectives::by_name;
+use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+use haru_cmaes::strategy::{CmaesAlgo, CmaesAlgoOptimizer};
+use nalgebra::DMatrix;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+fn spawn_workers(addr: SocketAddr, count: usize) -> Vec<thread::JoinHandle<usize>> {
+    (0..count)
+        .map(|_| thread::spawn(move || Worker::with_builtins().run(addr).unwrap()))
+        .collect()
+}
+
+/// Sends one raw protocol line and reads the answer.
+fn exchange(stream: &mut TcpStream, reader: &mut impl BufRead, line: &str) -> String {
+    writeln!(stream, "{}", line).unwrap();
+    let mut reply = String::new();
+    reader.read_line(&mut reply).unwrap();
+    reply.trim().to_string()
+}
+
+#[test]
+fn workers_evaluate_like_local_objective() {
+    let coordinator =
+        Coordinator::bind("127.0.0.1:0", "rosenbrock", Duration::from_secs(10)).unwrap();
+    let workers = spawn_workers(coordinator.local_addr(), 4);
+    let objective = by_name("rosenbrock", 6).unwrap();
+
+    let params = CmaesParams::new()
+        .and_then(|p| p.set_popsize(16))
+        .and_then(|p| p.set_xstart(6, 0.0))
+        .unwrap();
+    let cmaes = CmaesAlgo::new(params).unwrap();
+    let mut state = CmaesState::init_state(&cmaes.params).unwrap();
+    for _ in 0..20 {
+        let mut pop = cmaes.ask(&mut state).unwrap();
+        let values = coordinator.evaluate(&pop).unwrap();
+        assert_eq!(values, objective.cost(&pop));
+        state = cmaes
+            .tell(state, &mut pop, &mut Fitness { values })
+            .unwrap();
+    }
+
+    drop(coordinator);
+    let evaluations: usize = workers.into_iter().map(|w| w.join().unwrap()).sum();
+    assert_eq!(evaluations, 20 * 16);
+}
+
+#[test]
+fn silent_worker_is_dropped_and_its_task_redispatched() {
+    let coordinator =
+        Coordinator::bind("127.0.0.1:0", "sphere", Duration::from_millis(200)).unwrap();
+    let addr = coordinator.local_addr();
+
+    // Takes the first task and never answers
+    let mut silent = TcpStream::connect(addr).unwrap();
+    let mut silent_reader = BufReader::new(silent.try_clone().unwrap());
+    let pop = PopulationY {
+        y: DMatrix::from_row_slice(3, 2, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]),
+    };
+    thread::scope(|s| {
+        let evaluation = s.spawn(|| coordinator.evaluate(&pop).unwrap());
+        let task = loop {
+            let reply = exchange(&mut silent, &mut silent_reader, r#"{"type":"ready"}"#);
+            if reply.contains("task") {
+                break reply;
+            }
+        };
+        assert!(task.contains(r#""objective":"sphere""#));
+
+        let workers = spawn_workers(addr, 2);
+        assert_eq!(evaluation.join().unwrap().as_slice(), &[5.0, 25.0, 61.0]);
+        drop(workers);
+    });
+
+    // The coordinator closed the silent connection
+    let mut rest = String::new();
+    assert_eq!(silent_reader.read_line(&mut rest).unwrap_or(0), 0);
+}
+
+#[test]
+fn idle_workers_are_told_to_wait_then_done() {
+    let coordinator = Coordinator::bind("127.0.0.1:0", "sphere", Duration::from_secs(10)).unwrap();
+    let mut stream = TcpStream::connect(coordinator.local_addr()).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+    let reply = exchange(&mut stream, &mut reader, r#"{"type":"ready"}"#);
+    assert_eq!(reply, r#"{"type":"wait","ms":10}"#);
+    assert_eq!(coordinator.workers(), 1);
+    // Answers to unknown tasks are ignored
+    let reply = exchange(
+        &mut stream,
+        &mut reader,
+        r#"{"type":"result","id":42,"value":1.0}"#,
+    );
+    assert_eq!(reply, r#"{"type":"wait","ms":10}"#);
+
+    coordinator.close();
+    let reply = exchange(&mut stream, &mut reader, r#"{"type":"ready"}"#);
+    assert_eq!(reply, r#"{"type":"done"}"#);
+}
+
+#[test]
+fn non_finite_values_keep_their_sign() {
+    let coordinator = Coordinator::bind("127.0.0.1:0", "sphere", Duration::from_secs(10)).unwrap();
+    let mut stream = TcpStream::connect(coordinator.local_addr()).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let params = CmaesParams::new()
+        .and_then(|p| p.set_popsize(3))
+        .and_then(|p| p.set_xstart(2, 0.0))
+        .unwrap();
+    let cmaes = CmaesAlgo::new(params).unwrap();
+    let mut state = CmaesState::init_state(&cmaes.params).unwrap();
+    let mut pop = cmaes.ask(&mut state).unwrap();
+
+    let values = thread::scope(|s| {
+        let evaluation = s.spawn(|| coordinator.evaluate(&pop).unwrap());
+        let mut line = r#"{"type":"ready"}"#.to_string();
+        for value in [r#""inf""#, r#""nan""#, "2.5"] {
+            let id = loop {
+                let reply = exchange(&mut stream, &mut reader, &line);
+                line = r#"{"type":"ready"}"#.to_string();
+                if let CoordinatorMessage::Task { id, .. } = serde_json::from_str(&reply).unwrap() {
+                    break id;
+                }
+            };
+            line = format!(r#"{{"type":"result","id":{},"value":{}}}"#, id, value);
+        }
+        exchange(&mut stream, &mut reader, &line);
+        evaluation.join().unwrap()
+    });
+    assert_eq!(values[0], f32::INFINITY);
+    assert!(values[1].is_nan());
+    assert_eq!(values[2], 2.5);
+
+    let message = WorkerMessage::Result {
+        id: 1,
+        value: f32::NEG_INFINITY,
+    };
+    assert_eq!(
+        serde_json::to_string(&message).unwrap(),
+        r#"{"type":"result","id":1,"value":"-inf"}"#
+    );
+
+    // Ranked finite first, NaN last
+    let state = cmaes
+        .tell(state, &mut pop, &mut Fitness { values })
+        .unwrap();
+    assert_eq!(state.best_y_fit[0], 2.5);
+}
+
+#[test]
+fn unregistered_objective_fails_the_worker_only() {
+    let coordinator =
+        Coordinator::bind("127.0.0.1:0", "custom", Duration::from_millis(200)).unwrap();
+    let addr = coordinator.local_addr();
+    let pop = PopulationY {
+        y: DMatrix::from_row_slice(2, 2, &[1.0, 2.0, 3.0, 4.0]),
+    };
+
+    thread::scope(|s| {
+        let evaluation = s.spawn(|| coordinator.evaluate(&pop).unwrap());
+        let error = Worker::with_builtins().run(addr).unwrap_err();
+        assert!(error.to_string().contains("'custom' is not registered"));
+
+        // Its task goes to the next worker after the timeout
+        s.spawn(|| {
+            Worker::new()
+                .register("custom", |dim| by_name("sphere", dim))
+                .run(addr)
+                .unwrap()
+        });
+        assert_eq!(evaluation.join().unwrap().as_slice(), &[5.0, 25.0]);
+        coordinator.close();
+    });
+}
+
+#[test]
+fn closing_fails_a_pending_evaluation() {
+    let coordinator = Coordinator::bind("127.0.0.1:0", "sphere", Duration::from_secs(10)).unwrap();
+    let pop = PopulationY {
+        y: DMatrix::from_row_slice(1, 2, &[1.0, 2.0]),
+    };
+    thread::scope(|s| {
+        let evaluation = s.spawn(|| coordinator.evaluate(&pop));
+        thread::sleep(Duration::from_millis(50));
+        coordinator.close();
+        assert!(evaluation.join().unwrap().is_err());
+    });
+}
@@ -0,0 +1,120 @@
+//! Property tests of invariants that every `ask` and `tell` must keep.
+
+use haru_cmaes::fitness::{FitnessEvaluator, MinOrMax};
+use haru_cmaes::objectives::{Ellipsoid, Rosenbrock};
+use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+use haru_cmaes::strategy::{CmaesAlgo, CmaesAlgoOptimizer};
+use nalgebra::{Cholesky, DMatrix, DVector};
+use proptest::prelude::*;
+
+const GENERATIONS: usize = 15;
+
+fn params() -> impl Strategy<Value = CmaesParams> {
+    (2usize..=8, 4i32..=20, 0.1f32..3.0, -5f32..5., any::<bool>()).prop_map(
+        |(dim, popsize, sigma, origin, only_diag)| {
+            CmaesParams::new()
+                .and_then(|p| p.set_popsize(popsize))
+                .and_then(|p| p.set_xstart(dim, origin))
+                .and_then(|p| p.set_sigma(sigma))
+                .and_then(|p| p.set_only_diag(only_diag))
+                .unwrap()
+        },
+    )
+}
+
+fn max_abs(m: &DMatrix<f32>) -> f32 {
+    m.iter().fold(0f32, |acc, x| acc.max(x.abs()))
+}
+
+fn check_state(state: &CmaesState) -> Result<(), TestCaseError> {
+    prop_assert!(
+        state.sigma.is_finite() && state.sigma > 0.0,
+        "sigma {}",
+        state.sigma
+    );
+    let cov = &state.cov;
+    prop_assert!(cov.iter().all(|c| c.is_finite()), "cov {}", cov);
+    let asym = max_abs(&(cov - cov.transpose()));
+    prop_assert!(asym <= 1e-5 * max_abs(cov), "cov not symmetric {}", cov);
+    prop_assert!(
+        Cholesky::new(cov.clone()).is_some(),
+        "cov not positive definite {}",
+        cov
+    );
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn recombination_weights_sum_to_one(params in params()) {
+        let positive: f32 = params.weights.iter().filter(|&&w| w > 0.0).sum();
+        prop_assert!((positive - 1.0).abs() < 1e-5, "weights {}", params.weights);
+        prop_assert!(params.weights.rows(0, params.mu as usize).iter().all(|&w| w > 0.0));
+        prop_assert!(params.mueff >= 1.0 && params.mueff <= params.mu as f32 + 1e-4);
+    }
+
+    #[test]
+    fn tell_keeps_distribution_valid(params in params(), seed in any::<u64>(), rosenbrock in any::<bool>()) {
+        fastrand::seed(seed);
+        let dim = params.xstart.len();
+        let cmaes = CmaesAlgo::new(params).unwrap();
+        let mut state = CmaesState::init_state(&cmaes.params).unwrap();
+        for _ in 0..GENERATIONS {
+            let mut pop = cmaes.ask(&mut state).unwrap();
+
+            // The decomposition used by ask whitens the covariance
+            let n = state.cov.nrows();
+            let whitened = &state.inv_sqrt * &state.cov * &state.inv_sqrt;
+            let error = max_abs(&(whitened - DMatrix::identity(n, n)));
+            prop_assert!(error < 1e-3, "inv_sqrt * cov * inv_sqrt off identity by {}", error);
+
+            let mut fitness = if rosenbrock {
+                Rosenbrock { obj_dim: dim, dir: MinOrMax::Min }.evaluate(&pop).unwrap()
+            } else {
+                Ellipsoid { obj_dim: dim, dir: MinOrMax::Min }.evaluate(&pop).unwrap()
+            };
+            state = cmaes.tell(state, &mut pop, &mut fitness).unwrap();
+            check_state(&state)?;
+        }
+    }
+
+    #[test]
+    fn ask_samples_follow_distribution(
+        dim in 2usize..=5,
+        sigma in 0.1f32..3.0,
+        origin in -5f32..5.,
+        seed in any::<u64>(),
+    ) {
+        fastrand::seed(seed);
+        let samples = 4000;
+        let params = CmaesParams::new()
+            .and_then(|p| p.set_popsize(samples))
+            .and_then(|p| p.set_xstart(dim, origin))
+            .and_then(|p| p.set_sigma(sigma))
+            .unwrap();
+        let cmaes = CmaesAlgo::new(params).unwrap();
+        let mut state = CmaesState::init_state(&cmaes.params).unwrap();
+        // Random positive definite covariance with condition at most 10
+        let a = DMatrix::from_fn(dim, dim, |_, _| fastrand::f32() - 0.5);
+        state.cov = &a * a.transpose() + DMatrix::identity(dim, dim) * 0.5;
+        let pop = cmaes.ask(&mut state).unwrap();
+
+        let k = samples as f32;
+        let mean: DVector<f32> = pop.y.row_mean().transpose();
+        let centered = DMatrix::from_rows(
+            &pop.y.row_iter().map(|row| row - mean.transpose()).collect::<Vec<_>>(),
+        );
+        let cov = centered.transpose() * &centered / (k - 1.);
+        let expected = &state.cov * (sigma * sigma);
+
+        // Five standard errors of the sample mean and covariance
+        let scale = max_abs(&expected);
+        let mean_error = (&mean - &state.mean).amax();
+        prop_assert!(mean_error < 5. * (scale / k).sqrt(), "mean off by {}", mean_error);
+        let cov_error = max_abs(&(cov - &expected));
+        prop_assert!(cov_error < 5. * scale * (2. / k).sqrt(), "cov off by {}", cov_error);
+    }
+}
@@ -0,0 +1,199 @@
+//! Checks the CMA-ES update equations against recorded purecma trajectories.
+//!
+//! The fixtures in `tests/fixtures` are written by `assets/purecma_fixtures.py`.
+//! Each generation is replayed from its recorded state, so errors do not
+//! accumulate over the trajectory.
+//!
+//! The checked-in fixtures were recorded with the script's former transcription
+//! of purecma, not with `cma.purecma` itself. The replay tests run on them by
+//! default; `fixtures_come_from_purecma` stays ignored until they are
+//! re-recorded with the `cma` package installed.
+
+use haru_cmaes::fitness::{Fitness, PopulationY, PopulationZ};
+use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+use haru_cmaes::strategy::{CmaesAlgo, CmaesAlgoOptimizer};
+use nalgebra::{DMatrix, DVector};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Fixture {
+    generator: String,
+    function: String,
+    dim: usize,
+    popsize: i32,
+    generations: Vec<Generation>,
+}
+
+#[derive(Deserialize)]
+struct Generation {
+    before: Before,
+    z: Vec<Vec<f64>>,
+    x: Vec<Vec<f64>>,
+    f: Vec<f64>,
+    after: After,
+}
+
+#[derive(Deserialize)]
+struct Before {
+    counteval: i32,
+    mean: Vec<f64>,
+    sigma: f64,
+    cov: Vec<Vec<f64>>,
+    ps: Vec<f64>,
+    pc: Vec<f64>,
+    inv_sqrt: Vec<Vec<f64>>,
+    eig_vecs: Vec<Vec<f64>>,
+    eig_vals: Vec<f64>,
+}
+
+#[derive(Deserialize)]
+struct After {
+    mean: Vec<f64>,
+    sigma: f64,
+    cov: Vec<Vec<f64>>,
+    ps: Vec<f64>,
+    pc: Vec<f64>,
+}
+
+const FIXTURES: [&str; 3] = [
+    include_str!("fixtures/purecma_sphere_4d.json"),
+    include_str!("fixtures/purecma_ellipsoid_4d.json"),
+    include_str!("fixtures/purecma_rosenbrock_3d.json"),
+];
+
+fn vector(v: &[f64]) -> DVector<f32> {
+    DVector::from_iterator(v.len(), v.iter().map(|&x| x as f32))
+}
+
+fn matrix(rows: &[Vec<f64>]) -> DMatrix<f32> {
+    DMatrix::from_row_iterator(
+        rows.len(),
+        rows[0].len(),
+        rows.iter().flatten().map(|&x| x as f32),
+    )
+}
+
+/// Asserts `actual` matches `expected` up to `tol` relative to the largest expected entry.
+fn assert_close(actual: &[f32], expected: &[f32], tol: f32, what: &str) {
+    let scale = expected
+        .iter()
+        .fold(f32::MIN_POSITIVE, |m, x| m.max(x.abs()));
+    for (a, e) in actual.iter().zip(expected) {
+        assert!(
+            (a - e).abs() <= tol * scale,
+            "{}: got {:?}, expected {:?}",
+            what,
+            actual,
+            expected
+        );
+    }
+}
+
+fn setup(fixture: &Fixture) -> (CmaesAlgo, CmaesState) {
+    let params = CmaesParams::new()
+        .and_then(|p| p.set_popsize(fixture.popsize))
+        .and_then(|p| p.set_xstart(fixture.dim, 0.0))
+        .and_then(|p| p.set_sigma(fixture.generations[0].before.sigma as f32))
+        .unwrap();
+    let cmaes = CmaesAlgo::new(params).unwrap();
+    let state = CmaesState::init_state(&cmaes.params).unwrap();
+    (cmaes, state)
+}
+
+/// Overwrites the distribution of `state` with the recorded one.
+fn load(state: &mut CmaesState, before: &Before, popsize: i32) {
+    state.mean = vector(&before.mean);
+    state.sigma = before.sigma as f32;
+    state.cov = matrix(&before.cov);
+    state.ps = vector(&before.ps);
+    state.pc = vector(&before.pc);
+    state.inv_sqrt = matrix(&before.inv_sqrt);
+    state.eig_vecs = matrix(&before.eig_vecs);
+    state.eig_vals = vector(&before.eig_vals);
+    state.g = before.counteval / popsize;
+    state.evals_count = before.counteval;
+}
+
+#[test]
+#[ignore = "fixtures must be re-recorded with assets/purecma_fixtures.py and cma.purecma"]
+fn fixtures_come_from_purecma() {
+    for text in FIXTURES {
+        let fixture: Fixture = serde_json::from_str(text).unwrap();
+        assert_eq!(fixture.generator, "purecma", "{}", fixture.function);
+    }
+}
+
+#[test]
+fn fixtures_are_complete() {
+    for text in FIXTURES {
+        let fixture: Fixture = serde_json::from_str(text).unwrap();
+        assert!(fixture.generations.len() >= 30, "{}", fixture.function);
+        for gen in &fixture.generations {
+            assert_eq!(gen.z.len(), fixture.popsize as usize);
+            assert_eq!(gen.x.len(), gen.f.len());
+        }
+    }
+}
+
+#[test]
+fn sampling_matches_purecma() {
+    for text in FIXTURES {
+        let fixture: Fixture = serde_json::from_str(text).unwrap();
+        let (cmaes, mut state) = setup(&fixture);
+        for gen in &fixture.generations {
+            load(&mut state, &gen.before, fixture.popsize);
+            let y = cmaes.scale_z(&state, &PopulationZ { z: matrix(&gen.z) });
+            let expected = matrix(&gen.x);
+            for (row, exp) in y.y.row_iter().zip(expected.row_iter()) {
+                let row: Vec<f32> = row.iter().copied().collect();
+                let exp: Vec<f32> = exp.iter().copied().collect();
+                assert_close(&row, &exp, 1e-4, &format!("{} x", fixture.function));
+            }
+        }
+    }
+}
+
+#[test]
+fn tell_matches_purecma() {
+    for text in FIXTURES {
+        let fixture: Fixture = serde_json::from_str(text).unwrap();
+        let (cmaes, mut state) = setup(&fixture);
+        for (g, gen) in fixture.generations.iter().enumerate() {
+            load(&mut state, &gen.before, fixture.popsize);
+            let mut pop = PopulationY { y: matrix(&gen.x) };
+            let mut fitness = Fitness {
+                values: vector(&gen.f),
+            };
+            state = cmaes.tell(state, &mut pop, &mut fitness).unwrap();
+
+            let what = |field: &str| format!("{} generation {} {}", fixture.function, g, field);
+            let after = &gen.after;
+            assert_close(
+                state.mean.as_slice(),
+                vector(&after.mean).as_slice(),
+                1e-4,
+                &what("mean"),
+            );
+            assert_close(&[state.sigma], &[after.sigma as f32], 1e-4, &what("sigma"));
+            assert_close(
+                state.ps.as_slice(),
+                vector(&after.ps).as_slice(),
+                1e-3,
+                &what("ps"),
+            );
+            assert_close(
+                state.pc.as_slice(),
+                vector(&after.pc).as_slice(),
+                1e-3,
+                &what("pc"),
+            );
+            assert_close(
+                state.cov.as_slice(),
+                matrix(&after.cov).as_slice(),
+                1e-3,
+                &what("cov"),
+            );
+        }
+    }
+}
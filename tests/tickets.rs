@@ -0,0 +1,201 @@
+//! Checks the ticket driver against plain ask/tell and its straggler policies.
+
+use haru_cmaes::fitness::{FitnessEvaluator, MinOrMax, PopulationY};
+use haru_cmaes::objectives::Ellipsoid;
+use haru_cmaes::params::{CmaesParams, CmaesParamsValidator};
+use haru_cmaes::state::{CmaesState, CmaesStateLogic};
+use haru_cmaes::strategy::{CmaesAlgo, CmaesAlgoOptimizer};
+use haru_cmaes::tickets::{Stragglers, Submission, TicketDriver};
+use nalgebra::DMatrix;
+use std::time::Duration;
+
+const DIM: usize = 4;
+const GENERATIONS: i32 = 20;
+
+fn setup() -> (CmaesAlgo, CmaesState) {
+    let params = CmaesParams::new()
+        .and_then(|p| p.set_popsize(8))
+        .and_then(|p| p.set_xstart(DIM, 1.0))
+        .unwrap();
+    let cmaes = CmaesAlgo::new(params).unwrap();
+    let state = CmaesState::init_state(&cmaes.params).unwrap();
+    (cmaes, state)
+}
+
+fn ellipsoid(x: &[f32]) -> f32 {
+    let objective = Ellipsoid {
+        obj_dim: DIM,
+        dir: MinOrMax::Min,
+    };
+    let pop = PopulationY {
+        y: DMatrix::from_row_slice(1, x.len(), x),
+    };
+    objective.evaluate(&pop).unwrap().values[0]
+}
+
+#[test]
+fn out_of_order_results_match_ask_tell() {
+    fastrand::seed(7);
+    let (cmaes, mut expected) = setup();
+    let objective = Ellipsoid {
+        obj_dim: DIM,
+        dir: MinOrMax::Min,
+    };
+    for _ in 0..GENERATIONS {
+        let mut pop = cmaes.ask(&mut expected).unwrap();
+        let mut fitness = objective.evaluate(&pop).unwrap();
+        expected = cmaes.tell(expected, &mut pop, &mut fitness).unwrap();
+    }
+    // The driver samples the next generation right after telling
+    cmaes.ask(&mut expected).unwrap();
+
+    // Same samples, results submitted in shuffled order
+    fastrand::seed(7);
+    let (cmaes, state) = setup();
+    let mut driver = TicketDriver::new(cmaes, state, Stragglers::Wait).unwrap();
+    let mut shuffle = fastrand::Rng::with_seed(3);
+    while driver.state().g < GENERATIONS {
+        let mut tickets = Vec::new();
+        while let Some(ticket) = driver.ask_one().unwrap() {
+            tickets.push(ticket);
+        }
+        assert_eq!(tickets.len(), 8);
+        shuffle.shuffle(&mut tickets);
+        let last = tickets.pop().unwrap();
+        for ticket in tickets {
+            let value = ellipsoid(ticket.x.as_slice());
+            assert_eq!(
+                driver.submit(ticket.id, value).unwrap(),
+                Submission::Pending
+            );
+        }
+        let value = ellipsoid(last.x.as_slice());
+        assert_eq!(driver.submit(last.id, value).unwrap(), Submission::Told);
+    }
+
+    let state = driver.into_state();
+    assert_eq!(state.evals_count, expected.evals_count);
+    assert_eq!(state.mean, expected.mean);
+    assert_eq!(state.cov, expected.cov);
+    assert_eq!(state.sigma, expected.sigma);
+}
+
+#[test]
+fn reissued_candidates_are_told_once() {
+    let (cmaes, state) = setup();
+    let mut driver = TicketDriver::new(cmaes, state, Stragglers::Reissue(Duration::ZERO)).unwrap();
+    let first: Vec<_> = (0..8).map(|_| driver.ask_one().unwrap().unwrap()).collect();
+
+    // The late candidate is handed out again under a new ticket
+    let again = driver.ask_one().unwrap().unwrap();
+    assert_eq!(again.x, first[0].x);
+    assert_ne!(again.id, first[0].id);
+
+    let value = ellipsoid(again.x.as_slice());
+    assert_eq!(driver.submit(again.id, value).unwrap(), Submission::Pending);
+    assert_eq!(
+        driver.submit(first[0].id, value).unwrap(),
+        Submission::Stale
+    );
+    for ticket in &first[1..7] {
+        let value = ellipsoid(ticket.x.as_slice());
+        assert_eq!(
+            driver.submit(ticket.id, value).unwrap(),
+            Submission::Pending
+        );
+    }
+    let value = ellipsoid(first[7].x.as_slice());
+    assert_eq!(driver.submit(first[7].id, value).unwrap(), Submission::Told);
+    // Both evaluations of the reissued candidate were spent
+    assert_eq!(driver.state().evals_count, 9);
+
+    // Answers repeated are dropped uncounted, unknown tickets rejected
+    assert_eq!(driver.submit(first[1].id, 0.0).unwrap(), Submission::Stale);
+    assert_eq!(driver.state().evals_count, 9);
+    assert!(driver.submit(1000, 0.0).is_err());
+}
+
+#[test]
+fn resampling_replaces_stragglers() {
+    let (cmaes, state) = setup();
+    let mut driver = TicketDriver::new(cmaes, state, Stragglers::Resample).unwrap();
+    let straggler = driver.ask_one().unwrap().unwrap();
+    let others: Vec<_> = (0..8).map(|_| driver.ask_one().unwrap().unwrap()).collect();
+    assert_eq!(driver.in_flight(), 9);
+
+    for ticket in &others {
+        let value = ellipsoid(ticket.x.as_slice());
+        driver.submit(ticket.id, value).unwrap();
+    }
+    assert_eq!(driver.state().g, 1);
+    let value = ellipsoid(straggler.x.as_slice());
+    assert_eq!(
+        driver.submit(straggler.id, value).unwrap(),
+        Submission::Stale
+    );
+    assert_eq!(driver.state().evals_count, 9);
+}
+
+#[test]
+fn waiting_hands_out_nothing_more() {
+    let (cmaes, state) = setup();
+    let mut driver = TicketDriver::new(cmaes, state, Stragglers::Wait).unwrap();
+    for _ in 0..8 {
+        assert!(driver.ask_one().unwrap().is_some());
+    }
+    assert!(driver.ask_one().unwrap().is_none());
+}
+
+#[test]
+fn budget_truncates_the_last_generation() {
+    let params = CmaesParams::new()
+        .and_then(|p| p.set_popsize(8))
+        .and_then(|p| p.set_xstart(DIM, 1.0))
+        .and_then(|p| p.set_max_evals(12))
+        .unwrap();
+    let cmaes = CmaesAlgo::new(params).unwrap();
+    let state = CmaesState::init_state(&cmaes.params).unwrap();
+    let mut driver = TicketDriver::new(cmaes, state, Stragglers::Wait).unwrap();
+
+    for _ in 0..8 {
+        let ticket = driver.ask_one().unwrap().unwrap();
+        driver
+            .submit(ticket.id, ellipsoid(ticket.x.as_slice()))
+            .unwrap();
+    }
+    assert_eq!(driver.state().g, 1);
+
+    // Only the evaluations left are handed out
+    let last: Vec<_> = (0..4).map(|_| driver.ask_one().unwrap().unwrap()).collect();
+    assert!(driver.ask_one().unwrap().is_none());
+    for ticket in &last[..3] {
+        let value = ellipsoid(ticket.x.as_slice());
+        assert_eq!(
+            driver.submit(ticket.id, value).unwrap(),
+            Submission::Pending
+        );
+    }
+    let value = ellipsoid(last[3].x.as_slice());
+    assert_eq!(driver.submit(last[3].id, value).unwrap(), Submission::Told);
+    assert_eq!(driver.state().g, 2);
+    assert_eq!(driver.state().evals_count, 12);
+    assert!(driver.budget.exhausted().is_some());
+    assert!(driver.ask_one().unwrap().is_none());
+}
+
+#[test]
+fn non_finite_results_rank_last() {
+    let (cmaes, state) = setup();
+    let mut driver = TicketDriver::new(cmaes, state, Stragglers::Wait).unwrap();
+    let tickets: Vec<_> = (0..8).map(|_| driver.ask_one().unwrap().unwrap()).collect();
+    for (k, ticket) in tickets.iter().enumerate() {
+        let value = match k {
+            0 => f32::NAN,
+            1 => f32::INFINITY,
+            _ => ellipsoid(ticket.x.as_slice()),
+        };
+        driver.submit(ticket.id, value).unwrap();
+    }
+    assert_eq!(driver.state().g, 1);
+    assert!(driver.state().best_y_fit[0].is_finite());
+}